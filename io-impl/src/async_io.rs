@@ -1,8 +1,11 @@
-use std::{ffi::CStr, io};
+use std::{
+    ffi::CStr,
+    io::{self, IoSlice, IoSliceMut},
+};
 
-use io_trait::{AsyncFile, AsyncIo};
+use io_trait::{AsyncFile, AsyncIo, OpenOptions, Request};
 
-use crate::async_traits::{File, Operation, Overlapped};
+use crate::async_traits::{self, File, Operation};
 
 #[cfg(target_family = "windows")]
 use crate::windows::*;
@@ -10,63 +13,395 @@ use crate::windows::*;
 #[cfg(target_family = "unix")]
 use crate::unix::*;
 
-struct AFile {
-    file: File<Os>,
-    overlapped: Overlapped<Os>,
-}
+/// A file opened by [`RealAsyncIo`]. Several operations can be in progress at a time.
+pub struct RealAsyncFile(File<Os>);
 
-impl AsyncFile for AFile {
+impl AsyncFile for RealAsyncFile {
     type Operation<'a> = Operation<'a, Os>;
 
-    fn read<'a>(
-        &'a mut self,
+    fn read<'a>(&'a self, offset: u64, buffer: &'a mut [u8]) -> io::Result<Self::Operation<'a>> {
+        self.0.read(offset, buffer)
+    }
+
+    fn write<'a>(&'a self, offset: u64, buffer: &'a [u8]) -> io::Result<Self::Operation<'a>> {
+        self.0.write(offset, buffer)
+    }
+
+    fn read_vectored<'a>(
+        &'a self,
         offset: u64,
-        buffer: &'a mut [u8],
+        buffers: &'a mut [IoSliceMut<'a>],
     ) -> io::Result<Self::Operation<'a>> {
-        self.file.read(&mut self.overlapped, offset, buffer)
+        self.0.read_vectored(offset, buffers)
     }
 
-    fn write<'a>(&'a mut self, offset: u64, buffer: &'a [u8]) -> io::Result<Self::Operation<'a>> {
-        self.file.write(&mut self.overlapped, offset, buffer)
+    fn write_vectored<'a>(
+        &'a self,
+        offset: u64,
+        buffers: &'a [IoSlice<'a>],
+    ) -> io::Result<Self::Operation<'a>> {
+        self.0.write_vectored(offset, buffers)
     }
 }
 
-struct AIo();
+/// Asynchronous file I/O of the operating system: io_uring on Linux, if it's available, POSIX
+/// AIO on other Unix systems and overlapped I/O on Windows.
+#[derive(Default)]
+pub struct RealAsyncIo(());
+
+impl RealAsyncIo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AsyncIo for RealAsyncIo {
+    type File = RealAsyncFile;
+
+    fn open_with(&self, path: &CStr, options: &OpenOptions) -> io::Result<Self::File> {
+        File::open_with(path, options).map(RealAsyncFile)
+    }
 
-impl AsyncIo for AIo {
-    type File = AFile;
+    fn submit<'a>(
+        &self,
+        requests: Vec<Request<'a, Self::File>>,
+    ) -> io::Result<Vec<Operation<'a, Os>>> {
+        let requests = requests
+            .into_iter()
+            .map(|request| match request {
+                Request::Read {
+                    file,
+                    offset,
+                    buffer,
+                } => Request::Read {
+                    file: &file.0,
+                    offset,
+                    buffer,
+                },
+                Request::Write {
+                    file,
+                    offset,
+                    buffer,
+                } => Request::Write {
+                    file: &file.0,
+                    offset,
+                    buffer,
+                },
+            })
+            .collect();
+        async_traits::submit(requests)
+    }
 
-    fn create(&self, path: &CStr) -> io::Result<Self::File> {
-        Ok(AFile {
-            file: File::create(path)?,
-            overlapped: Overlapped::default(),
-        })
+    fn wait_any(&self, operations: &mut [Operation<'_, Os>]) -> Option<(usize, io::Result<usize>)> {
+        async_traits::wait_any(operations)
     }
 
-    fn open(&self, path: &CStr) -> io::Result<Self::File> {
-        Ok(AFile {
-            file: File::open(path)?,
-            overlapped: Default::default(),
-        })
+    fn wait_all(&self, operations: &mut [Operation<'_, Os>]) -> Vec<io::Result<usize>> {
+        async_traits::wait_all(operations)
     }
 }
 
 #[cfg(test)]
-mod test {
-    use std::{ffi::CString, thread::yield_now};
+pub mod test {
+    use std::{
+        ffi::{CStr, CString},
+        fs,
+        future::Future,
+        io::{self, IoSlice, IoSliceMut},
+        pin::pin,
+        sync::{mpsc, Arc},
+        task::{Context, Poll, Wake},
+        thread::{self, yield_now, Thread},
+        time::Duration,
+    };
+
+    use super::{Os, RealAsyncIo};
+    use crate::async_traits::{submit, wait_all, wait_any, AsyncTrait, File};
+    use io_trait::{AsyncFile, AsyncIo, AsyncOperation, OpenOptions, OperationResult, Request};
 
-    use super::AIo;
-    use io_trait::{AsyncFile, AsyncIo, AsyncOperation, OperationResult};
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Runs a future on the current thread, parking it until the future is woken.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    /// Sends a message when it's woken.
+    struct Signal(mpsc::Sender<()>);
+
+    impl Wake for Signal {
+        fn wake(self: Arc<Self>) {
+            let _ = self.0.send(());
+        }
+    }
+
+    /// A pipe that reads stay pending on until it's written: a FIFO on Unix and a named pipe
+    /// on Windows.
+    struct Fifo {
+        path: CString,
+        #[cfg(target_family = "windows")]
+        server: fs::File,
+    }
+
+    impl Fifo {
+        #[cfg(target_family = "unix")]
+        fn new(name: &str) -> Self {
+            let path = CString::new(name).unwrap();
+            let _ = fs::remove_file(name);
+            assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o644) }, 0);
+            Self { path }
+        }
+        #[cfg(target_family = "windows")]
+        fn new(name: &str) -> Self {
+            use std::{os::windows::io::FromRawHandle, ptr::null_mut};
+
+            use crate::windows_api::{CreateNamedPipeA, INVALID_HANDLE_VALUE, PIPE_ACCESS_DUPLEX};
+
+            let path = CString::new(format!(r"\\.\pipe\{name}")).unwrap();
+            let server = unsafe {
+                CreateNamedPipeA(
+                    path.as_ptr(),
+                    PIPE_ACCESS_DUPLEX,
+                    0,
+                    1,
+                    64,
+                    64,
+                    0,
+                    null_mut(),
+                )
+            };
+            assert_ne!(server, INVALID_HANDLE_VALUE);
+            let server = unsafe { fs::File::from_raw_handle(server) };
+            Self { path, server }
+        }
+        fn path(&self) -> &CStr {
+            &self.path
+        }
+        /// Writes `data` from another handle.
+        fn write(&self, data: &[u8]) {
+            #[cfg(target_family = "unix")]
+            fs::write(self.path.to_str().unwrap(), data).unwrap();
+            #[cfg(target_family = "windows")]
+            io::Write::write_all(&mut &self.server, data).unwrap();
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    impl Drop for Fifo {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(self.path.to_str().unwrap());
+        }
+    }
+
+    fn wait(mut operation: impl AsyncOperation) -> io::Result<usize> {
+        operation.wait(None).ready().unwrap()
+    }
+
+    /// Writes and reads a regular file through the backend `T`.
+    pub fn round_trip<T: AsyncTrait>(name: &str) {
+        let path = CString::new(name).unwrap();
+        let origin = b"Hello async io!";
+        {
+            let mut options = OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            let file = File::<T>::open_with(&path, &options).unwrap();
+            let n = wait(file.write(0, &origin[..6]).unwrap()).unwrap();
+            assert_eq!(n, 6);
+            let n = wait(file.write(6, &origin[6..]).unwrap()).unwrap();
+            assert_eq!(n, origin.len() - 6);
+        }
+        {
+            let file = File::<T>::open_with(&path, OpenOptions::new().read(true)).unwrap();
+            let mut buffer = [0u8; 64];
+            let n = wait(file.read(0, &mut buffer).unwrap()).unwrap();
+            assert_eq!(&buffer[..n], origin);
+            let n = wait(file.read(6, &mut buffer).unwrap()).unwrap();
+            assert_eq!(&buffer[..n], &origin[6..]);
+            let result = file
+                .read(0, &mut buffer)
+                .unwrap()
+                .wait(Some(Duration::from_secs(10)));
+            assert!(matches!(result, OperationResult::Ok(15)));
+            // reading past the end isn't an error.
+            let n = wait(file.read(100, &mut buffer).unwrap()).unwrap();
+            assert_eq!(n, 0);
+            // a read-only file can't be written.
+            let result = file.write(0, origin).and_then(wait);
+            assert!(result.is_err());
+        }
+        {
+            let options = OpenOptions::new().read(true).write(true).clone();
+            let file = File::<T>::open_with(&path, &options).unwrap();
+            let buffers = [IoSlice::new(b"ab"), IoSlice::new(b""), IoSlice::new(b"cde")];
+            let n = wait(file.write_vectored(20, &buffers).unwrap()).unwrap();
+            assert_eq!(n, 5);
+            let (mut a, mut b, mut c) = ([0; 3], [0; 3], [0; 3]);
+            let mut buffers = [
+                IoSliceMut::new(&mut a),
+                IoSliceMut::new(&mut b),
+                IoSliceMut::new(&mut c),
+            ];
+            // the read stops at the end of the file.
+            let n = wait(file.read_vectored(19, &mut buffers).unwrap()).unwrap();
+            assert_eq!(n, 6);
+            assert_eq!((&a, &b), (b"\0ab", b"cde"));
+        }
+        {
+            let options = OpenOptions::new().read(true).write(true).clone();
+            let file = File::<T>::open_with(&path, &options).unwrap();
+            let blocks: Vec<_> = (0..100u8).map(|i| [i; 10]).collect();
+            let requests = blocks
+                .iter()
+                .enumerate()
+                .map(|(i, block)| Request::Write {
+                    file: &file,
+                    offset: i as u64 * 10,
+                    buffer: block,
+                })
+                .collect();
+            let mut writes = submit(requests).unwrap();
+            assert!(wait_all(&mut writes).into_iter().all(|r| r.unwrap() == 10));
+            drop(writes);
+            let mut buffer = [0; 1000];
+            let requests = buffer
+                .chunks_mut(10)
+                .enumerate()
+                .map(|(i, buffer)| Request::Read {
+                    file: &file,
+                    offset: i as u64 * 10,
+                    buffer,
+                })
+                .collect();
+            let mut reads = submit(requests).unwrap();
+            while let Some((i, result)) = wait_any(&mut reads) {
+                assert_eq!(result.unwrap(), 10);
+                reads.swap_remove(i);
+            }
+            drop(reads);
+            assert_eq!(buffer.to_vec(), blocks.concat());
+        }
+        fs::remove_file(name).unwrap();
+    }
+
+    /// A read from an empty FIFO stays pending until it's written by another handle.
+    pub fn timeout<T: AsyncTrait>(name: &str) {
+        let fifo = Fifo::new(name);
+        // a FIFO opened for reading and writing doesn't block on open.
+        let options = OpenOptions::new().read(true).write(true).clone();
+        let file = File::<T>::open_with(fifo.path(), &options).unwrap();
+        let mut buffer = [0; 5];
+        let mut operation = file.read(0, &mut buffer).unwrap();
+        let result = operation.wait(Some(Duration::from_millis(10)));
+        assert!(matches!(result, OperationResult::Pending));
+        fifo.write(b"Hello");
+        assert!(matches!(operation.wait(None), OperationResult::Ok(5)));
+        drop(operation);
+        assert_eq!(&buffer, b"Hello");
+        // the backend wakes the future when the FIFO is written later.
+        let future = file.read(0, &mut buffer).unwrap().future();
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                fifo.write(b"World");
+            });
+            assert_eq!(block_on(future).unwrap(), 5);
+        });
+        assert_eq!(&buffer, b"World");
+        // waiting doesn't replace the registered waker.
+        let (sender, receiver) = mpsc::channel();
+        let mut operation = file.read(0, &mut buffer).unwrap();
+        operation.set_waker(&Arc::new(Signal(sender)).into());
+        let result = operation.wait(Some(Duration::from_millis(10)));
+        assert!(matches!(result, OperationResult::Pending));
+        fifo.write(b"Again");
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(matches!(operation.get_result(), OperationResult::Ok(5)));
+        drop(operation);
+        assert_eq!(&buffer, b"Again");
+    }
+
+    /// Cancels a read that is pending on a FIFO and one that has completed.
+    pub fn cancel<T: AsyncTrait>(name: &str) {
+        let fifo = Fifo::new(name);
+        let options = OpenOptions::new().read(true).write(true).clone();
+        let file = File::<T>::open_with(fifo.path(), &options).unwrap();
+        let (mut buffer, mut other) = ([0; 5], [0; 5]);
+        let mut running = file.read(0, &mut buffer).unwrap();
+        let mut queued = file.read(0, &mut other).unwrap();
+        let canceled = thread::scope(|s| {
+            let (sender, receiver) = mpsc::channel();
+            let fifo = &fifo;
+            // `cancel` blocks until the read is done, so if it can't be canceled, the reads
+            // are completed instead of hanging the test.
+            s.spawn(move || {
+                if receiver.recv_timeout(Duration::from_secs(1)).is_err() {
+                    fifo.write(b"WorldWorld");
+                }
+            });
+            let canceled = queued.cancel();
+            let _ = sender.send(());
+            canceled
+        });
+        // POSIX AIO may not cancel a running read, but glibc runs the requests of a file one
+        // by one, so it cancels the one queued after it. Windows and io_uring cancel both.
+        assert!(canceled || cfg!(target_os = "macos"));
+        let result = queued.get_result();
+        drop(queued);
+        if canceled {
+            assert!(matches!(result, OperationResult::Err(_)));
+            assert_eq!(other, [0; 5]);
+        } else {
+            assert!(matches!(result, OperationResult::Ok(5)));
+        }
+        fifo.write(b"Hello");
+        assert!(matches!(running.wait(None), OperationResult::Ok(5)));
+        // a completed operation isn't canceled and keeps its result.
+        assert!(!running.cancel());
+        assert!(matches!(running.get_result(), OperationResult::Ok(5)));
+        drop(running);
+        if canceled {
+            assert_eq!(&buffer, b"Hello");
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip::<Os>("_test_round_trip.txt");
+    }
+
+    #[test]
+    fn test_timeout() {
+        timeout::<Os>("_test_timeout.fifo");
+    }
+
+    #[test]
+    fn test_cancel() {
+        cancel::<Os>("_test_cancel.fifo");
+    }
 
     #[test]
     fn test() {
-        let aio = AIo();
+        let aio = RealAsyncIo::new();
         //
         for _ in 0..1000 {
             let x: CString = CString::new("_test.txt").unwrap();
             let origin = b"Hello World!";
             {
-                let mut handle = aio.create(&x).unwrap();
+                let handle = aio.create(&x).unwrap();
                 let mut operation = handle.write(0, origin).unwrap();
                 loop {
                     match operation.get_result() {
@@ -88,7 +423,7 @@ mod test {
                 // assert_eq!(result, 12);
             }
             {
-                let mut handle = aio.open(&x).unwrap();
+                let handle = aio.open(&x).unwrap();
                 let mut buffer = [0u8; 1024];
                 {
                     let mut operation = handle.read(0, &mut buffer).unwrap();
@@ -118,12 +453,12 @@ mod test {
 
     #[test]
     fn test2() {
-        let aio = AIo();
+        let aio = RealAsyncIo::new();
         let x: CString = CString::new("_test2.txt").unwrap();
         let origin = "Hello, world!";
         for _ in 0..1000 {
             {
-                let mut file = aio.create(&x).unwrap();
+                let file = aio.create(&x).unwrap();
                 let mut operation = file.write(0, origin.as_bytes()).unwrap();
                 loop {
                     match operation.get_result() {
@@ -145,7 +480,7 @@ mod test {
         }
         for _ in 0..1000 {
             {
-                let mut file = aio.open(&x).unwrap();
+                let file = aio.open(&x).unwrap();
                 let mut buffer = [0u8; 1024];
                 let len;
                 {
@@ -172,11 +507,11 @@ mod test {
 
     #[test]
     fn test3() {
-        let aio = AIo();
+        let aio = RealAsyncIo::new();
         let x: CString = CString::new("_big_test.txt").unwrap();
         let origin = "Hello, world!".repeat(100);
         {
-            let mut file = aio.create(&x).unwrap();
+            let file = aio.create(&x).unwrap();
             let mut operation = file.write(0, origin.as_bytes()).unwrap();
             loop {
                 match operation.get_result() {
@@ -196,7 +531,7 @@ mod test {
             }
         }
         {
-            let mut file = aio.open(&x).unwrap();
+            let file = aio.open(&x).unwrap();
             let mut v = Vec::default();
             loop {
                 let mut buffer = [0u8; 1024];
@@ -226,4 +561,209 @@ mod test {
             assert_eq!(&v, origin.as_bytes());
         }
     }
+
+    #[test]
+    fn test_future() {
+        let aio = RealAsyncIo::new();
+        let x = CString::new("_test_future.txt").unwrap();
+        let origin = "Hello, world!".repeat(1000);
+        for _ in 0..100 {
+            block_on(async {
+                {
+                    let file = aio.create(&x).unwrap();
+                    let len = file
+                        .write(0, origin.as_bytes())
+                        .unwrap()
+                        .future()
+                        .await
+                        .unwrap();
+                    assert_eq!(len, origin.len());
+                }
+                let file = aio.open(&x).unwrap();
+                let mut buffer = vec![0; origin.len() + 1];
+                let len = file.read(0, &mut buffer).unwrap().future().await.unwrap();
+                assert_eq!(&buffer[..len], origin.as_bytes());
+            });
+        }
+        std::fs::remove_file("_test_future.txt").unwrap();
+    }
+
+    #[test]
+    fn test_open_modes() {
+        let aio = RealAsyncIo::new();
+        let x = CString::new("_test_open_modes.txt").unwrap();
+        let _ = std::fs::remove_file("_test_open_modes.txt");
+        assert_eq!(aio.open(&x).err().unwrap().kind(), io::ErrorKind::NotFound);
+        block_on(async {
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            {
+                let file = aio.open_with(&x, &options).unwrap();
+                file.write(0, b"Hello").unwrap().future().await.unwrap();
+                let mut buffer = [0; 16];
+                let len = file.read(0, &mut buffer).unwrap().future().await.unwrap();
+                assert_eq!(&buffer[..len], b"Hello");
+            }
+            assert_eq!(
+                aio.open_with(&x, &options).err().unwrap().kind(),
+                io::ErrorKind::AlreadyExists
+            );
+            {
+                // the file is updated in place.
+                let file = aio
+                    .open_with(&x, OpenOptions::new().read(true).write(true))
+                    .unwrap();
+                file.write(0, b"J").unwrap().future().await.unwrap();
+                let mut buffer = [0; 16];
+                let len = file.read(0, &mut buffer).unwrap().future().await.unwrap();
+                assert_eq!(&buffer[..len], b"Jello");
+            }
+            {
+                let file = aio.open_with(&x, OpenOptions::new().append(true)).unwrap();
+                file.write(0, b"!").unwrap().future().await.unwrap();
+            }
+        });
+        assert_eq!(std::fs::read("_test_open_modes.txt").unwrap(), b"Jello!");
+        assert_eq!(
+            aio.open_with(&x, OpenOptions::new().read(true).truncate(true))
+                .err()
+                .unwrap()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+        std::fs::remove_file("_test_open_modes.txt").unwrap();
+    }
+
+    #[test]
+    fn test_concurrent() {
+        const N: usize = 32;
+        const BLOCK: usize = 4096;
+        let aio = RealAsyncIo::new();
+        let x = CString::new("_test_concurrent.txt").unwrap();
+        let file = aio
+            .open_with(
+                &x,
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true),
+            )
+            .unwrap();
+        let blocks: Vec<Vec<u8>> = (0..N).map(|i| vec![i as u8; BLOCK]).collect();
+        block_on(async {
+            // all operations are submitted before any of them is awaited.
+            let writes: Vec<_> = blocks
+                .iter()
+                .enumerate()
+                .map(|(i, block)| file.write((i * BLOCK) as u64, block).unwrap())
+                .collect();
+            for write in writes {
+                assert_eq!(write.future().await.unwrap(), BLOCK);
+            }
+            let mut buffers = vec![vec![0; BLOCK]; N];
+            let reads: Vec<_> = buffers
+                .iter_mut()
+                .enumerate()
+                .rev()
+                .map(|(i, buffer)| file.read((i * BLOCK) as u64, buffer).unwrap())
+                .collect();
+            for read in reads {
+                assert_eq!(read.future().await.unwrap(), BLOCK);
+            }
+            assert_eq!(buffers, blocks);
+        });
+        drop(file);
+        std::fs::remove_file("_test_concurrent.txt").unwrap();
+    }
+
+    #[test]
+    fn test_vectored() {
+        let aio = RealAsyncIo::new();
+        let x = CString::new("_test_vectored.txt").unwrap();
+        block_on(async {
+            {
+                let file = aio.create(&x).unwrap();
+                let payload = "Hello, world!".repeat(1000);
+                let header = (payload.len() as u32).to_le_bytes();
+                let buffers = [IoSlice::new(&header), IoSlice::new(payload.as_bytes())];
+                let len = file.write_vectored(0, &buffers).unwrap().future().await;
+                assert_eq!(len.unwrap(), 4 + payload.len());
+            }
+            let file = aio.open(&x).unwrap();
+            let mut header = [0; 4];
+            let mut payload = vec![0; 20_000];
+            let mut buffers = [IoSliceMut::new(&mut header), IoSliceMut::new(&mut payload)];
+            let len = file.read_vectored(0, &mut buffers).unwrap().future().await;
+            assert_eq!(len.unwrap(), 13_004);
+            assert_eq!(u32::from_le_bytes(header), 13_000);
+            assert_eq!(&payload[..13], b"Hello, world!");
+            {
+                // the buffers are appended together.
+                let file = aio.open_with(&x, OpenOptions::new().append(true)).unwrap();
+                let buffers = [IoSlice::new(b"ab"), IoSlice::new(b"cd")];
+                let len = file.write_vectored(0, &buffers).unwrap().future().await;
+                assert_eq!(len.unwrap(), 4);
+            }
+            let data = std::fs::read("_test_vectored.txt").unwrap();
+            assert_eq!(data.len(), 13_008);
+            assert!(data.ends_with(b"abcd"));
+        });
+        std::fs::remove_file("_test_vectored.txt").unwrap();
+    }
+
+    #[test]
+    fn test_batch() {
+        const N: usize = 64;
+        const BLOCK: usize = 1024;
+        let aio = RealAsyncIo::new();
+        let x = CString::new("_test_batch.txt").unwrap();
+        let file = aio
+            .open_with(
+                &x,
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true),
+            )
+            .unwrap();
+        let blocks: Vec<Vec<u8>> = (0..N).map(|i| vec![i as u8; BLOCK]).collect();
+        let requests = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| Request::Write {
+                file: &file,
+                offset: (i * BLOCK) as u64,
+                buffer: block,
+            })
+            .collect();
+        let mut writes = aio.submit(requests).unwrap();
+        for result in aio.wait_all(&mut writes) {
+            assert_eq!(result.unwrap(), BLOCK);
+        }
+        drop(writes);
+        let mut buffers = vec![vec![0; BLOCK]; N];
+        let requests = buffers
+            .iter_mut()
+            .enumerate()
+            .map(|(i, buffer)| Request::Read {
+                file: &file,
+                offset: (i * BLOCK) as u64,
+                buffer,
+            })
+            .collect();
+        let mut reads = aio.submit(requests).unwrap();
+        let mut completed = 0;
+        while let Some((i, result)) = aio.wait_any(&mut reads) {
+            assert_eq!(result.unwrap(), BLOCK);
+            reads.swap_remove(i);
+            completed += 1;
+        }
+        assert_eq!(completed, N);
+        drop(reads);
+        assert_eq!(buffers, blocks);
+        drop(file);
+        std::fs::remove_file("_test_batch.txt").unwrap();
+    }
 }
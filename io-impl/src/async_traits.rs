@@ -1,31 +1,244 @@
-use std::{ffi::CStr, io};
+use std::{
+    ffi::CStr,
+    io::{self, IoSlice, IoSliceMut},
+    ptr::copy_nonoverlapping,
+    sync::{Arc, Mutex},
+    task::Waker,
+    thread::yield_now,
+    time::{Duration, Instant},
+};
 
-use io_trait::{AsyncOperation, OperationResult};
+use io_trait::{AsyncOperation, OpenOptions, OperationResult, Request};
+
+/// Wakes a task when an operation completes.
+#[derive(Default)]
+pub struct Notifier(Mutex<Option<Waker>>);
+
+impl Notifier {
+    pub fn register(&self, waker: &Waker) {
+        *self.0.lock().unwrap() = Some(waker.clone());
+    }
+    /// Registers `waker` in place of the registered waker, which is returned.
+    #[cfg(target_os = "linux")]
+    pub fn replace(&self, waker: &Waker) -> Option<Waker> {
+        self.0.lock().unwrap().replace(waker.clone())
+    }
+    /// Registers `waker` again after [`Notifier::replace`] or wakes it if the notifier has been
+    /// notified since.
+    #[cfg(target_os = "linux")]
+    pub fn restore(&self, waker: Option<Waker>) {
+        let mut registered = self.0.lock().unwrap();
+        if registered.is_some() {
+            *registered = waker;
+        } else if let Some(waker) = waker {
+            drop(registered);
+            waker.wake();
+        }
+    }
+    pub fn notify(&self) {
+        let waker = self.0.lock().unwrap().take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// A point in time to wait until, or no limit.
+#[derive(Clone, Copy)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self(timeout.and_then(|timeout| Instant::now().checked_add(timeout)))
+    }
+    /// Returns the time left or `None` if there is no limit.
+    pub fn remaining(self) -> Option<Duration> {
+        self.0
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+    pub fn has_expired(self) -> bool {
+        self.remaining() == Some(Duration::ZERO)
+    }
+}
 
 pub trait AsyncTrait {
     type Handle: Copy;
+    /// The control block of one operation.
     type Overlapped;
     fn overlapped_default() -> Self::Overlapped;
     fn close(handle: Self::Handle);
-    fn cancel(handle: Self::Handle, overlapped: &mut Self::Overlapped);
+    /// Cancels the operation and blocks until it's done, see [`AsyncOperation::cancel`].
+    fn cancel(handle: Self::Handle, overlapped: &mut Self::Overlapped) -> bool;
     fn get_result(handle: Self::Handle, overlapped: &mut Self::Overlapped) -> OperationResult;
-    fn open(path: &CStr, create: bool) -> io::Result<Self::Handle>;
-    fn init_overlapped(
-        handle: Self::Handle,
-        overlapped: &mut Self::Overlapped,
-        offset: u64,
-        buffer: &[u8],
-    );
-    fn read(
-        handle: Self::Handle,
-        overlapped: &mut Self::Overlapped,
-        buffer: &mut [u8],
-    ) -> io::Result<()>;
-    fn write(
+    /// `options` are already checked by [`OpenOptions::check`].
+    fn open(path: &CStr, options: &OpenOptions) -> io::Result<Self::Handle>;
+    /// Submits operations at once. If one of them can't be submitted, the submitted ones are
+    /// canceled and the error is returned.
+    fn submit(submissions: &mut [Submission<Self>]) -> io::Result<()>
+    where
+        Self: Sized;
+    /// Makes sure that `notifier` is notified when the operation completes, see
+    /// [`AsyncOperation::set_waker`]. It can be called several times. The default does nothing
+    /// for backends that always notify the notifier given on submission.
+    fn watch(_overlapped: &mut Self::Overlapped, _notifier: &Arc<Notifier>) {}
+    /// Blocks until one of the operations may have completed or `timeout` elapses. It can
+    /// return early.
+    fn suspend(_overlapped: &[&Self::Overlapped], _timeout: Option<Duration>) {
+        yield_now();
+    }
+    /// Blocks until the operation completes or `timeout` elapses, see
+    /// [`AsyncOperation::wait`].
+    fn wait(
         handle: Self::Handle,
         overlapped: &mut Self::Overlapped,
-        buffer: &[u8],
-    ) -> io::Result<()>;
+        timeout: Option<Duration>,
+    ) -> OperationResult {
+        let deadline = Deadline::new(timeout);
+        loop {
+            let result = Self::get_result(handle, overlapped);
+            if !matches!(result, OperationResult::Pending) || deadline.has_expired() {
+                return result;
+            }
+            Self::suspend(&[overlapped], deadline.remaining());
+        }
+    }
+}
+
+/// Buffers of an operation. They must outlive the operation, but the slice of them may be
+/// released after submission.
+pub enum Buffers<'a, 'b> {
+    Read(&'a mut [IoSliceMut<'b>]),
+    Write(&'a [IoSlice<'b>]),
+}
+
+impl<'b> Buffers<'_, 'b> {
+    pub fn is_write(&self) -> bool {
+        matches!(self, Buffers::Write(_))
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            Buffers::Read(buffers) => buffers.len(),
+            Buffers::Write(buffers) => buffers.len(),
+        }
+    }
+    pub fn get(&self, i: usize) -> &[u8] {
+        match self {
+            Buffers::Read(buffers) => &buffers[i],
+            Buffers::Write(buffers) => &buffers[i],
+        }
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> + Clone + use<'_, 'b> {
+        (0..self.len()).map(|i| self.get(i))
+    }
+    /// `IoSlice` and `IoSliceMut` are ABI compatible with `iovec` on Unix.
+    pub fn as_ptr(&self) -> *const u8 {
+        match self {
+            Buffers::Read(buffers) => buffers.as_ptr() as *const u8,
+            Buffers::Write(buffers) => buffers.as_ptr() as *const u8,
+        }
+    }
+}
+
+/// One contiguous buffer in place of several buffers, for backends without general vectored
+/// operations. The operation is then one request, so like `pwritev`, it's appended as a whole
+/// and a short or failed write doesn't leave later buffers written.
+pub struct Bounce {
+    buffer: Vec<u8>,
+    /// Read buffers that the data is copied to.
+    targets: Vec<(*mut u8, usize)>,
+}
+
+impl Bounce {
+    /// Returns `None` for one buffer or none, which is used directly. Write buffers are copied
+    /// at once.
+    pub fn new(buffers: &mut Buffers) -> Option<Self> {
+        if buffers.len() <= 1 {
+            return None;
+        }
+        let len = buffers.iter().map(|buffer| buffer.len()).sum();
+        Some(match buffers {
+            Buffers::Read(buffers) => Self {
+                buffer: vec![0; len],
+                targets: buffers
+                    .iter_mut()
+                    .map(|buffer| (buffer.as_mut_ptr(), buffer.len()))
+                    .collect(),
+            },
+            Buffers::Write(buffers) => {
+                let mut buffer = Vec::with_capacity(len);
+                for b in buffers.iter() {
+                    buffer.extend_from_slice(b);
+                }
+                Self {
+                    buffer,
+                    targets: Vec::new(),
+                }
+            }
+        })
+    }
+    /// Returns the buffer of the request: the bounce buffer or the only one of `buffers`.
+    pub fn buffer<'a>(bounce: &'a Option<Self>, buffers: &'a Buffers) -> &'a [u8] {
+        match bounce {
+            Some(bounce) => &bounce.buffer,
+            None => buffers.iter().next().unwrap_or_default(),
+        }
+    }
+    /// Copies `len` read bytes to the read buffers. The read buffers outlive the operation,
+    /// see [`Buffers`].
+    pub fn scatter(&self, len: usize) {
+        let mut data = &self.buffer[..len];
+        for &(target, target_len) in &self.targets {
+            let (head, tail) = data.split_at(target_len.min(data.len()));
+            unsafe { copy_nonoverlapping(head.as_ptr(), target, head.len()) };
+            data = tail;
+        }
+    }
+}
+
+/// An operation for [`AsyncTrait::submit`].
+pub struct Submission<'a, 'b, T: AsyncTrait> {
+    pub handle: T::Handle,
+    pub overlapped: &'a mut T::Overlapped,
+    pub offset: u64,
+    pub buffers: Buffers<'a, 'b>,
+    pub notifier: &'a Arc<Notifier>,
+}
+
+impl<'b, T: AsyncTrait> Submission<'_, 'b, T> {
+    /// Returns the same submission with a backend specific control block, see `Linux`.
+    #[cfg(target_os = "linux")]
+    pub fn map<U: AsyncTrait<Handle = T::Handle>>(
+        &mut self,
+        f: impl FnOnce(&mut T::Overlapped) -> &mut U::Overlapped,
+    ) -> Submission<'_, 'b, U> {
+        Submission {
+            handle: self.handle,
+            overlapped: f(self.overlapped),
+            offset: self.offset,
+            buffers: match &mut self.buffers {
+                Buffers::Read(buffers) => Buffers::Read(buffers),
+                Buffers::Write(buffers) => Buffers::Write(buffers),
+            },
+            notifier: self.notifier,
+        }
+    }
+}
+
+/// Submits operations one by one, canceling the submitted ones if one of them fails.
+#[cfg(target_family = "windows")]
+pub fn submit_each<T: AsyncTrait>(
+    submissions: &mut [Submission<T>],
+    mut f: impl FnMut(&mut Submission<T>) -> io::Result<()>,
+) -> io::Result<()> {
+    for i in 0..submissions.len() {
+        if let Err(e) = f(&mut submissions[i]) {
+            for s in &mut submissions[..i] {
+                T::cancel(s.handle, s.overlapped);
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
 }
 
 //
@@ -39,63 +252,186 @@ impl<T: AsyncTrait> Drop for File<T> {
     }
 }
 
-#[repr(transparent)]
-pub struct Overlapped<T: AsyncTrait>(T::Overlapped);
+pub struct Overlapped<T: AsyncTrait> {
+    overlapped: T::Overlapped,
+    notifier: Arc<Notifier>,
+}
 
 impl<T: AsyncTrait> Default for Overlapped<T> {
     fn default() -> Self {
-        Self(T::overlapped_default())
+        Self {
+            overlapped: T::overlapped_default(),
+            notifier: Arc::default(),
+        }
     }
 }
 
 impl<T: AsyncTrait> File<T> {
-    pub fn create(file_name: &CStr) -> io::Result<Self> {
-        T::open(file_name, true).map(File)
+    pub fn open_with(file_name: &CStr, options: &OpenOptions) -> io::Result<Self> {
+        options.check()?;
+        T::open(file_name, options).map(File)
+    }
+
+    pub fn read<'a>(&'a self, offset: u64, buffer: &'a mut [u8]) -> io::Result<Operation<'a, T>> {
+        self.read_vectored(offset, &mut [IoSliceMut::new(buffer)])
     }
-    pub fn open(file_name: &CStr) -> io::Result<Self> {
-        T::open(file_name, false).map(File)
+
+    pub fn write<'a>(&'a self, offset: u64, buffer: &'a [u8]) -> io::Result<Operation<'a, T>> {
+        self.write_vectored(offset, &[IoSlice::new(buffer)])
     }
 
-    pub fn read<'a>(
-        &'a mut self,
-        overlapped: &'a mut Overlapped<T>,
+    pub fn read_vectored<'a>(
+        &'a self,
         offset: u64,
-        buffer: &'a mut [u8], // it's important that the buffer has the same life time as the overlapped!
+        buffers: &mut [IoSliceMut<'a>],
     ) -> io::Result<Operation<'a, T>> {
-        T::init_overlapped(self.0, &mut overlapped.0, offset, buffer);
-        T::read(self.0, &mut overlapped.0, buffer).map(|_| Operation {
-            handle: self,
-            overlapped,
-        })
+        self.submit(offset, Buffers::Read(buffers))
     }
 
-    pub fn write<'a>(
-        &'a mut self,
-        overlapped: &'a mut Overlapped<T>,
+    pub fn write_vectored<'a>(
+        &'a self,
         offset: u64,
-        buffer: &'a [u8], // it's important that the buffer has the same life time as the overlapped!
+        buffers: &[IoSlice<'a>],
     ) -> io::Result<Operation<'a, T>> {
-        T::init_overlapped(self.0, &mut overlapped.0, offset, buffer);
-        T::write(self.0, &mut overlapped.0, buffer).map(|_| Operation {
-            handle: self,
-            overlapped,
+        self.submit(offset, Buffers::Write(buffers))
+    }
+
+    fn submit<'a>(&'a self, offset: u64, buffers: Buffers<'_, 'a>) -> io::Result<Operation<'a, T>> {
+        let mut operation = Operation::new(self);
+        let o = &mut *operation.overlapped;
+        let result = T::submit(&mut [Submission {
+            handle: self.0,
+            overlapped: &mut o.overlapped,
+            offset,
+            buffers,
+            notifier: &o.notifier,
+        }]);
+        if let Err(e) = result {
+            // the backend cancels the submitted requests if the submission fails.
+            operation.submitted = false;
+            return Err(e);
+        }
+        Ok(operation)
+    }
+}
+
+/// Submits operations at once, see [`AsyncTrait::submit`].
+pub fn submit<'a, T: AsyncTrait>(
+    requests: Vec<Request<'a, File<T>>>,
+) -> io::Result<Vec<Operation<'a, T>>> {
+    let mut requests: Vec<_> = requests
+        .into_iter()
+        .map(|request| match request {
+            Request::Read {
+                file,
+                offset,
+                buffer,
+            } => (Operation::new(file), offset, Err([IoSliceMut::new(buffer)])),
+            Request::Write {
+                file,
+                offset,
+                buffer,
+            } => (Operation::new(file), offset, Ok([IoSlice::new(buffer)])),
+        })
+        .collect();
+    let mut submissions: Vec<_> = requests
+        .iter_mut()
+        .map(|(operation, offset, buffers)| {
+            let o = &mut *operation.overlapped;
+            Submission {
+                handle: operation.handle.0,
+                overlapped: &mut o.overlapped,
+                offset: *offset,
+                buffers: match buffers {
+                    Ok(buffers) => Buffers::Write(buffers),
+                    Err(buffers) => Buffers::Read(buffers),
+                },
+                notifier: &o.notifier,
+            }
         })
+        .collect();
+    if let Err(e) = T::submit(&mut submissions) {
+        // the submitted operations are already canceled.
+        for (operation, ..) in &mut requests {
+            operation.submitted = false;
+        }
+        return Err(e);
+    }
+    Ok(requests
+        .into_iter()
+        .map(|(operation, ..)| operation)
+        .collect())
+}
+
+/// Blocks until one of `operations` completes, see [`io_trait::AsyncIo::wait_any`].
+pub fn wait_any<T: AsyncTrait>(
+    operations: &mut [Operation<T>],
+) -> Option<(usize, io::Result<usize>)> {
+    if operations.is_empty() {
+        return None;
+    }
+    loop {
+        for (i, operation) in operations.iter_mut().enumerate() {
+            if let Some(result) = operation.get_result().ready() {
+                return Some((i, result));
+            }
+        }
+        let overlapped: Vec<_> = operations
+            .iter()
+            .map(|o| &o.overlapped.overlapped)
+            .collect();
+        T::suspend(&overlapped, None);
     }
 }
 
+/// Blocks until all `operations` complete, see [`io_trait::AsyncIo::wait_all`].
+pub fn wait_all<T: AsyncTrait>(operations: &mut [Operation<T>]) -> Vec<io::Result<usize>> {
+    operations
+        .iter_mut()
+        .map(|operation| operation.wait(None).ready().unwrap())
+        .collect()
+}
+
+/// An operation in progress. It borrows its buffer, so the buffer outlives the operation,
+/// and owns its control block, so other operations on the same file can be in flight. The
+/// control block is boxed, so it keeps its address while the operation is moved.
 pub struct Operation<'a, T: AsyncTrait> {
-    handle: &'a mut File<T>,
-    overlapped: &'a mut Overlapped<T>,
+    handle: &'a File<T>,
+    overlapped: Box<Overlapped<T>>,
+    submitted: bool,
+}
+
+impl<'a, T: AsyncTrait> Operation<'a, T> {
+    fn new(handle: &'a File<T>) -> Self {
+        Self {
+            handle,
+            overlapped: Box::default(),
+            submitted: true,
+        }
+    }
 }
 
 impl<T: AsyncTrait> Drop for Operation<'_, T> {
     fn drop(&mut self) {
-        T::cancel(self.handle.0, &mut self.overlapped.0);
+        if self.submitted {
+            T::cancel(self.handle.0, &mut self.overlapped.overlapped);
+        }
     }
 }
 
 impl<T: AsyncTrait> AsyncOperation for Operation<'_, T> {
     fn get_result(&mut self) -> OperationResult {
-        T::get_result(self.handle.0, &mut self.overlapped.0)
+        T::get_result(self.handle.0, &mut self.overlapped.overlapped)
+    }
+    fn set_waker(&mut self, waker: &Waker) {
+        let o = &mut *self.overlapped;
+        o.notifier.register(waker);
+        T::watch(&mut o.overlapped, &o.notifier);
+    }
+    fn wait(&mut self, timeout: Option<Duration>) -> OperationResult {
+        T::wait(self.handle.0, &mut self.overlapped.overlapped, timeout)
+    }
+    fn cancel(&mut self) -> bool {
+        T::cancel(self.handle.0, &mut self.overlapped.overlapped)
     }
 }
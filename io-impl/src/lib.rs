@@ -1,70 +1,196 @@
 #[cfg(any(target_family = "windows", target_family = "unix"))]
 mod async_io;
+#[cfg(any(target_family = "windows", target_family = "unix"))]
 mod async_traits;
 mod unix;
+mod uring;
 mod windows;
 mod windows_api;
 
+#[cfg(any(target_family = "windows", target_family = "unix"))]
+pub use async_io::{RealAsyncFile, RealAsyncIo};
+
 use std::{
-    env::{args, current_dir, set_current_dir, Args},
+    env::{self, args, current_dir, set_current_dir, Args, VarError, Vars},
     fs::{self, create_dir, File},
-    io::{self, Stdout},
+    io::{self, Stderr, Stdin, Stdout},
+    path::{Path, PathBuf},
     time::Instant,
 };
 
-use io_trait::Io;
+use io_trait::{Io, OpenOptions};
 
 #[derive(Default)]
 pub struct RealIo();
 
+#[cfg(any(target_family = "windows", target_family = "unix"))]
+impl RealIo {
+    /// Returns asynchronous file I/O of the operating system.
+    pub fn async_io(&self) -> RealAsyncIo {
+        RealAsyncIo::new()
+    }
+}
+
 impl Io for RealIo {
     type Args = Args;
+    type Vars = Vars;
 
+    type Stdin = Stdin;
     type Stdout = Stdout;
+    type Stderr = Stderr;
     type File = File;
     type Metadata = fs::Metadata;
     type DirEntry = fs::DirEntry;
+    type ReadDir<'a> = fs::ReadDir;
     type Instant = Instant;
 
     fn args(&self) -> Self::Args {
         args()
     }
 
-    fn create(&self, path: &str) -> io::Result<Self::File> {
-        File::create(path)
+    fn var(&self, key: &str) -> Result<String, VarError> {
+        env::var(key)
+    }
+
+    fn vars(&self) -> Self::Vars {
+        env::vars()
+    }
+
+    fn set_var(&self, key: &str, value: &str) {
+        env::set_var(key, value)
+    }
+
+    fn remove_var(&self, key: &str) {
+        env::remove_var(key)
     }
 
-    fn open(&self, path: &str) -> io::Result<Self::File> {
-        File::open(path)
+    fn open_with(&self, path: impl AsRef<Path>, options: &OpenOptions) -> io::Result<Self::File> {
+        fs::OpenOptions::from(options).open(path)
     }
 
-    fn metadata(&self, path: &str) -> io::Result<fs::Metadata> {
+    fn metadata(&self, path: impl AsRef<Path>) -> io::Result<fs::Metadata> {
         fs::metadata(path)
     }
 
-    fn read_dir(&self, path: &str) -> io::Result<Vec<Self::DirEntry>> {
-        fs::read_dir(path)?.collect()
+    fn symlink_metadata(&self, path: impl AsRef<Path>) -> io::Result<fs::Metadata> {
+        fs::symlink_metadata(path)
     }
 
-    fn create_dir(&self, path: &str) -> io::Result<()> {
+    fn read_dir(&self, path: impl AsRef<Path>) -> io::Result<fs::ReadDir> {
+        fs::read_dir(path)
+    }
+
+    fn create_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
         create_dir(path)
     }
 
+    fn stdin(&self) -> Self::Stdin {
+        io::stdin()
+    }
+
     fn stdout(&self) -> Self::Stdout {
         io::stdout()
     }
 
+    fn stderr(&self) -> Self::Stderr {
+        io::stderr()
+    }
+
     fn now(&self) -> Instant {
         Instant::now()
     }
 
-    fn current_dir(&self) -> io::Result<String> {
-        current_dir().map(|x| x.to_string_lossy().to_string())
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        current_dir()
     }
 
-    fn set_current_dir(&self, path: &str) -> io::Result<()> {
+    fn set_current_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
         set_current_dir(path)
     }
+
+    fn remove_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn remove_dir_all(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn set_read_only(&self, path: impl AsRef<Path>, read_only: bool) -> io::Result<()> {
+        let mut permissions = fs::metadata(&path)?.permissions();
+        permissions.set_readonly(read_only);
+        fs::set_permissions(path, permissions)
+    }
+
+    fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn symlink(&self, original: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()> {
+        symlink(original.as_ref(), link.as_ref())
+    }
+
+    fn hard_link(&self, original: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()> {
+        fs::hard_link(original, link)
+    }
+
+    fn read_link(&self, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn sync_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        sync_dir(path.as_ref())
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    File::open(path)?.sync_all()
+}
+
+/// Directory entries are durable without syncing the directory.
+#[cfg(not(target_family = "unix"))]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    if fs::metadata(path)?.is_dir() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::NotADirectory,
+            "not a directory",
+        ))
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(target_family = "windows")]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    use std::os::windows::fs;
+    let target = link.parent().unwrap_or(Path::new("")).join(original);
+    if target.is_dir() {
+        fs::symlink_dir(original, link)
+    } else {
+        fs::symlink_file(original, link)
+    }
+}
+
+#[cfg(not(any(target_family = "unix", target_family = "windows")))]
+fn symlink(_original: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "symbolic links are not supported",
+    ))
 }
 
 #[cfg(test)]
@@ -72,15 +198,26 @@ mod test {
     use std::{
         fs,
         io::{Read, Write},
+        path::Path,
     };
 
-    use io_trait::{File, Io};
+    use io_trait::{File, FileType, Io, Metadata, OpenOptions};
 
     #[test]
     fn test_arg() {
         let io = super::RealIo::default();
         let a = io.args().collect::<Vec<_>>();
-        assert!(a.len() > 0);
+        assert!(!a.is_empty());
+    }
+
+    #[test]
+    fn test_var() {
+        let io = super::RealIo::default();
+        io.set_var("_TEST_IO_VAR", "test");
+        assert_eq!(io.var("_TEST_IO_VAR").unwrap(), "test");
+        assert!(io.vars().any(|(k, v)| k == "_TEST_IO_VAR" && v == "test"));
+        io.remove_var("_TEST_IO_VAR");
+        assert!(io.var("_TEST_IO_VAR").is_err());
     }
 
     #[test]
@@ -100,11 +237,237 @@ mod test {
             assert_eq!(buf, b"test");
         }
         io.metadata("_test_file").unwrap();
-        io.read_dir(".").unwrap();
+        assert!(io.read_dir(".").unwrap().count() > 0);
+        assert!(!io.read_dir_vec(".").unwrap().is_empty());
         fs::remove_file("_test_file").unwrap();
         io.create_dir("_test_dir").unwrap();
         fs::remove_dir("_test_dir").unwrap();
+        let _ = io.stdin();
         let _ = io.stdout();
+        let _ = io.stderr();
+    }
+
+    #[test]
+    fn test_remove() {
+        let io = super::RealIo::default();
+        io.create_dir("_test_remove").unwrap();
+        io.create_dir("_test_remove/a").unwrap();
+        io.write("_test_remove/a/b.txt", b"test").unwrap();
+        io.write("_test_remove/c.txt", b"test").unwrap();
+        io.remove_file("_test_remove/c.txt").unwrap();
+        assert!(io.metadata("_test_remove/c.txt").is_err());
+        assert!(io.remove_dir("_test_remove").is_err());
+        io.remove_dir_all("_test_remove/a").unwrap();
+        io.remove_dir("_test_remove").unwrap();
+        assert!(io.metadata("_test_remove").is_err());
+    }
+
+    #[test]
+    fn test_rename() {
+        let io = super::RealIo::default();
+        let _ = io.remove_dir_all("_test_rename");
+        io.create_dir("_test_rename").unwrap();
+        let tmp_files = || {
+            io.read_dir("_test_rename")
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .filter(|p| p.extension().is_some_and(|e| e == "tmp"))
+                .count()
+        };
+        io.write("_test_rename/a", b"a").unwrap();
+        io.write_atomic("_test_rename/b", b"b").unwrap();
+        io.rename("_test_rename/a", "_test_rename/b").unwrap();
+        assert!(io.metadata("_test_rename/a").is_err());
+        assert_eq!(io.read("_test_rename/b").unwrap(), b"a");
+        io.write_atomic("_test_rename/b", b"c").unwrap();
+        assert_eq!(io.read("_test_rename/b").unwrap(), b"c");
+        assert_eq!(tmp_files(), 0);
+        // a directory can't be replaced, so the temporary file is removed.
+        io.create_dir("_test_rename/d").unwrap();
+        io.write("_test_rename/d/e", b"e").unwrap();
+        assert!(io.write_atomic("_test_rename/d", b"d").is_err());
+        assert_eq!(tmp_files(), 0);
+        io.remove_dir_all("_test_rename").unwrap();
+    }
+
+    #[test]
+    fn test_open_with() {
+        let io = super::RealIo::default();
+        io.write("_test_open_with", b"Hello").unwrap();
+        {
+            let mut file = io
+                .open_with("_test_open_with", OpenOptions::new().append(true))
+                .unwrap();
+            file.write_all(b", world!").unwrap();
+        }
+        assert_eq!(io.read("_test_open_with").unwrap(), b"Hello, world!");
+        assert!(io
+            .open_with(
+                "_test_open_with",
+                OpenOptions::new().write(true).create_new(true)
+            )
+            .is_err());
+        {
+            let mut file = io
+                .open_with("_test_open_with", OpenOptions::new().read(true).write(true))
+                .unwrap();
+            file.write_all(b"J").unwrap();
+            let mut buf = String::default();
+            file.read_to_string(&mut buf).unwrap();
+            assert_eq!(buf, "ello, world!");
+        }
+        {
+            let mut file = io.open("_test_open_with").unwrap();
+            assert!(file.write_all(b"x").is_err());
+        }
+        assert_eq!(io.read("_test_open_with").unwrap(), b"Jello, world!");
+        io.remove_file("_test_open_with").unwrap();
+    }
+
+    #[test]
+    fn test_read_only() {
+        let io = super::RealIo::default();
+        io.write("_test_read_only", b"test").unwrap();
+        io.set_read_only("_test_read_only", true).unwrap();
+        let m = io.metadata("_test_read_only").unwrap();
+        assert!(Metadata::is_read_only(&m));
+        assert_eq!(Metadata::file_type(&m), FileType::File);
+        assert!(Metadata::modified(&m).is_ok());
+        io.set_read_only("_test_read_only", false).unwrap();
+        assert!(!Metadata::is_read_only(
+            &io.metadata("_test_read_only").unwrap()
+        ));
+        io.remove_file("_test_read_only").unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_links() {
+        let io = super::RealIo::default();
+        let _ = io.remove_dir_all("_test_links");
+        io.create_dir("_test_links").unwrap();
+        io.write("_test_links/a.txt", b"test").unwrap();
+        io.symlink("a.txt", "_test_links/b.txt").unwrap();
+        io.hard_link("_test_links/a.txt", "_test_links/c.txt")
+            .unwrap();
+        assert_eq!(
+            io.read_link("_test_links/b.txt").unwrap(),
+            Path::new("a.txt")
+        );
+        assert!(Metadata::is_symlink(
+            &io.symlink_metadata("_test_links/b.txt").unwrap()
+        ));
+        assert!(Metadata::is_file(
+            &io.metadata("_test_links/b.txt").unwrap()
+        ));
+        io.write("_test_links/c.txt", b"link").unwrap();
+        assert_eq!(io.read("_test_links/b.txt").unwrap(), b"link");
+        io.remove_dir_all("_test_links").unwrap();
+    }
+
+    #[cfg(any(target_family = "windows", target_family = "unix"))]
+    #[test]
+    fn test_async_io() {
+        use io_trait::{AsyncFile, AsyncIo, AsyncOperation, OperationResult};
+        use std::{ffi::CString, thread::yield_now};
+        let aio = super::RealIo::default().async_io();
+        let path = CString::new("_test_async_io").unwrap();
+        let wait = |mut operation: <super::RealAsyncFile as AsyncFile>::Operation<'_>| loop {
+            match operation.get_result() {
+                OperationResult::Ok(len) => break len,
+                OperationResult::Pending => yield_now(),
+                OperationResult::Err(e) => panic!("{e}"),
+            }
+        };
+        let file = aio.create(&path).unwrap();
+        assert_eq!(wait(file.write(0, b"Hello").unwrap()), 5);
+        drop(file);
+        let file = aio.open(&path).unwrap();
+        let mut buffer = [0; 16];
+        assert_eq!(wait(file.read(0, &mut buffer).unwrap()), 5);
+        assert_eq!(&buffer[..5], b"Hello");
+        drop(file);
+        fs::remove_file("_test_async_io").unwrap();
+    }
+
+    #[test]
+    fn test_sync_set_len() {
+        let io = super::RealIo::default();
+        {
+            let mut file = io.create("_test_sync_set_len").unwrap();
+            file.write_all(b"Hello, world!").unwrap();
+            file.sync_data().unwrap();
+            file.set_len(5).unwrap();
+            file.sync_all().unwrap();
+        }
+        io.sync_dir(".").unwrap();
+        assert_eq!(io.read("_test_sync_set_len").unwrap(), b"Hello");
+        io.remove_file("_test_sync_set_len").unwrap();
+    }
+
+    #[test]
+    fn test_walk_dir() {
+        let io = super::RealIo::default();
+        let _ = io.remove_dir_all("_test_walk_dir");
+        io.write_recursively("_test_walk_dir/b/c.txt", b"c")
+            .unwrap();
+        io.write("_test_walk_dir/a.txt", b"a").unwrap();
+        let x = io
+            .walk_dir("_test_walk_dir")
+            .sort(true)
+            .contents_first(true)
+            .map(|e| e.unwrap().into_path())
+            .collect::<Vec<_>>();
+        let root = Path::new("_test_walk_dir");
+        assert_eq!(
+            x,
+            [
+                root.join("a.txt"),
+                root.join("b").join("c.txt"),
+                root.join("b"),
+                root.to_path_buf()
+            ]
+        );
+        let x = io
+            .walk_dir("_test_walk_dir")
+            .max_depth(1)
+            .filter(|e| Metadata::is_file(e.as_ref().unwrap().metadata()))
+            .count();
+        assert_eq!(x, 1);
+        io.remove_dir_all("_test_walk_dir").unwrap();
+    }
+
+    #[test]
+    fn test_glob() {
+        let io = super::RealIo::default();
+        let _ = io.remove_dir_all("_test_glob");
+        io.write_recursively("_test_glob/a/b.blk", b"b").unwrap();
+        io.write("_test_glob/c.blk", b"c").unwrap();
+        io.write("_test_glob/d.txt", b"d").unwrap();
+        let root = Path::new("_test_glob");
+        assert_eq!(
+            io_trait::glob(&io, "_test_glob/**/*.blk").unwrap(),
+            [root.join("a").join("b.blk"), root.join("c.blk")]
+        );
+        io.remove_dir_all("_test_glob").unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_non_utf8_path() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let io = super::RealIo::default();
+        let _ = io.remove_dir_all("_test_non_utf8");
+        io.create_dir("_test_non_utf8").unwrap();
+        let path = Path::new("_test_non_utf8").join(OsStr::from_bytes(b"\xff\xfe.txt"));
+        io.write(&path, b"test").unwrap();
+        let x = io
+            .read_dir("_test_non_utf8")
+            .unwrap()
+            .map(|v| v.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(x, [path]);
+        io.remove_dir_all("_test_non_utf8").unwrap();
     }
 
     #[test]
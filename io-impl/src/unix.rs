@@ -1,14 +1,22 @@
 #![cfg(target_family = "unix")]
 #![cfg(not(tarpaulin_include))]
 
-use std::{ffi::CStr, io, mem::zeroed, thread::yield_now};
+use std::{
+    ffi::CStr,
+    io,
+    mem::zeroed,
+    ptr::{null, null_mut},
+    sync::Arc,
+    time::Duration,
+};
 
-use io_trait::OperationResult;
+use io_trait::{OpenOptions, OperationResult};
 use libc::{
-    aio_cancel, aio_read, aio_return, aio_write, aiocb, c_int, close, open, AIO_NOTCANCELED,
+    aio_cancel, aio_read, aio_return, aio_suspend, aio_write, aiocb, c_int, close, lio_listio,
+    open, sysconf, timespec, _SC_AIO_LISTIO_MAX, ECANCELED, LIO_NOWAIT, LIO_READ, LIO_WRITE,
 };
 
-use crate::async_traits::AsyncTrait;
+use crate::async_traits::{AsyncTrait, Bounce, Buffers, Notifier, Submission};
 
 #[derive(Debug, PartialEq, Eq)]
 #[repr(transparent)]
@@ -21,6 +29,196 @@ fn aio_error(overlapped: &aiocb) -> AioError {
 
 pub struct Unix();
 
+/// glibc runs `sigev_notify_function` in a new thread when an operation completes or is
+/// canceled, so each completion costs a thread. Other systems don't support `SIGEV_THREAD`
+/// for AIO.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+mod notification {
+    use std::{mem::offset_of, ptr::null_mut, sync::Arc};
+
+    use libc::{aiocb, c_int, c_void, pthread_attr_t, sigevent, sigval, SIGEV_THREAD};
+
+    use crate::async_traits::Notifier;
+
+    /// glibc's `sigevent` with the `SIGEV_THREAD` fields of its union, which `libc` doesn't
+    /// expose.
+    #[repr(C)]
+    struct SigEvent {
+        sigev_value: sigval,
+        sigev_signo: c_int,
+        sigev_notify: c_int,
+        sigev_notify_function: Option<extern "C" fn(sigval)>,
+        sigev_notify_attributes: *mut pthread_attr_t,
+        pad: [u8; size_of::<sigevent>()
+            - offset_of!(sigevent, sigev_notify_thread_id)
+            - 2 * size_of::<usize>()],
+    }
+
+    const _: () = {
+        assert!(size_of::<SigEvent>() == size_of::<sigevent>());
+        assert!(align_of::<SigEvent>() == align_of::<sigevent>());
+        assert!(offset_of!(SigEvent, sigev_value) == offset_of!(sigevent, sigev_value));
+        assert!(offset_of!(SigEvent, sigev_signo) == offset_of!(sigevent, sigev_signo));
+        assert!(offset_of!(SigEvent, sigev_notify) == offset_of!(sigevent, sigev_notify));
+        assert!(
+            offset_of!(SigEvent, sigev_notify_function)
+                == offset_of!(sigevent, sigev_notify_thread_id)
+        );
+    };
+
+    /// After a failed `lio_listio`, a request that is not queued can't be told apart from one
+    /// that completed, so its notifier couldn't be released. glibc queues the requests of
+    /// `lio_listio` one by one anyway.
+    pub const LISTIO: bool = false;
+
+    /// The notifier is given on submission, so there is nothing to watch.
+    pub fn watch(_aiocb: &aiocb, _notifier: &Arc<Notifier>) {}
+
+    pub fn unwatch(_aiocb: &aiocb) {}
+
+    extern "C" fn notify(value: sigval) {
+        // SAFETY: the pointer is created by `Arc::into_raw` in `init`.
+        let notifier = unsafe { Arc::from_raw(value.sival_ptr as *const Notifier) };
+        notifier.notify();
+    }
+
+    pub fn init(overlapped: &mut aiocb, notifier: &Arc<Notifier>) {
+        let event = SigEvent {
+            sigev_value: sigval {
+                sival_ptr: Arc::into_raw(notifier.clone()) as *mut c_void,
+            },
+            sigev_signo: 0,
+            sigev_notify: SIGEV_THREAD,
+            sigev_notify_function: Some(notify),
+            sigev_notify_attributes: null_mut(),
+            pad: [0; _],
+        };
+        // SAFETY: the layouts are checked above.
+        unsafe {
+            (&raw mut overlapped.aio_sigevent)
+                .cast::<SigEvent>()
+                .write(event)
+        };
+    }
+
+    /// Releases the notifier if the operation has not been submitted.
+    pub fn release(overlapped: &aiocb) {
+        drop(unsafe {
+            Arc::from_raw(overlapped.aio_sigevent.sigev_value.sival_ptr as *const Notifier)
+        });
+    }
+}
+
+/// Other systems don't notify, so one thread waits for all the watched requests. It runs
+/// while there are some.
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+mod notification {
+    use std::{
+        sync::{Arc, Condvar, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use libc::aiocb;
+
+    use super::{aio_error, suspend, EINPROGRESS};
+    use crate::async_traits::Notifier;
+
+    pub const LISTIO: bool = true;
+
+    /// `aio_suspend` can't be interrupted portably, so a request that is watched while the
+    /// thread waits is seen after at most this time.
+    const RESCAN: Duration = Duration::from_millis(10);
+
+    struct State {
+        watched: Vec<(*const aiocb, Arc<Notifier>)>,
+        /// Control blocks that the thread is waiting for.
+        suspended: Vec<*const aiocb>,
+        running: bool,
+    }
+
+    // SAFETY: the control blocks are used only while they are watched or suspended, see
+    // `unwatch`.
+    unsafe impl Send for State {}
+
+    static STATE: Mutex<State> = Mutex::new(State {
+        watched: Vec::new(),
+        suspended: Vec::new(),
+        running: false,
+    });
+
+    /// Signaled when the thread stops waiting for `State::suspended`.
+    static RESUMED: Condvar = Condvar::new();
+
+    fn run() {
+        loop {
+            let mut state = STATE.lock().unwrap();
+            state.suspended.clear();
+            RESUMED.notify_all();
+            let mut done = Vec::new();
+            state.watched.retain(|(aiocb, notifier)| {
+                let pending = aio_error(unsafe { &**aiocb }) == EINPROGRESS;
+                if !pending {
+                    done.push(notifier.clone());
+                }
+                pending
+            });
+            let list: Vec<_> = state.watched.iter().map(|&(aiocb, _)| aiocb).collect();
+            state.suspended.clone_from(&list);
+            state.running = !list.is_empty();
+            drop(state);
+            // wakers may watch other operations, so they are called without the lock.
+            for notifier in done {
+                notifier.notify();
+            }
+            if list.is_empty() {
+                return;
+            }
+            suspend(&list, Some(RESCAN));
+        }
+    }
+
+    pub fn watch(aiocb: &aiocb, notifier: &Arc<Notifier>) {
+        if aio_error(aiocb) != EINPROGRESS {
+            notifier.notify();
+            return;
+        }
+        let aiocb = aiocb as *const aiocb;
+        let mut state = STATE.lock().unwrap();
+        if state.watched.iter().any(|&(a, _)| a == aiocb) {
+            return;
+        }
+        state.watched.push((aiocb, notifier.clone()));
+        if state.running {
+            return;
+        }
+        match thread::Builder::new().spawn(run) {
+            Ok(_) => state.running = true,
+            Err(_) => {
+                state.watched.pop();
+                drop(state);
+                // the operation is polled again.
+                notifier.notify();
+            }
+        }
+    }
+
+    /// Stops watching the control block, so it can be released. The request must be done,
+    /// so the thread stops waiting for it soon.
+    pub fn unwatch(aiocb: &aiocb) {
+        let aiocb = aiocb as *const aiocb;
+        let mut state = STATE.lock().unwrap();
+        state.watched.retain(|&(a, _)| a != aiocb);
+        while state.suspended.contains(&aiocb) {
+            state = RESUMED.wait(state).unwrap();
+        }
+    }
+
+    pub fn init(_overlapped: &mut aiocb, _notifier: &Arc<Notifier>) {}
+
+    pub fn release(_overlapped: &aiocb) {}
+}
+
 fn to_result(result: c_int) -> io::Result<c_int> {
     if result == -1 {
         return Err(io::Error::last_os_error());
@@ -32,68 +230,203 @@ fn to_operation_result(result: c_int) -> io::Result<()> {
     to_result(result).map(|_| ())
 }
 
+/// The request of an operation.
+pub struct Request {
+    aiocb: aiocb,
+    /// `aio_return` can be called only once, so its result is kept.
+    result: Option<Result<usize, c_int>>,
+    /// It's released when the request completes.
+    bounce: Option<Bounce>,
+}
+
+impl Request {
+    fn new(handle: c_int, offset: u64, buffers: &mut Buffers) -> Self {
+        let bounce = Bounce::new(buffers);
+        let buffer = Bounce::buffer(&bounce, buffers);
+        let mut aiocb: aiocb = unsafe { zeroed() };
+        aiocb.aio_fildes = handle;
+        aiocb.aio_lio_opcode = if buffers.is_write() {
+            LIO_WRITE
+        } else {
+            LIO_READ
+        };
+        aiocb.aio_buf = buffer.as_ptr() as *mut _;
+        aiocb.aio_nbytes = buffer.len();
+        aiocb.aio_offset = offset as i64;
+        Self {
+            aiocb,
+            result: None,
+            bounce,
+        }
+    }
+    fn result(&mut self) -> OperationResult {
+        if self.result.is_none() {
+            let error = aio_error(&self.aiocb);
+            if error == EINPROGRESS {
+                return OperationResult::Pending;
+            }
+            let len = unsafe { aio_return(&mut self.aiocb) };
+            self.result = Some(match error {
+                AioError(0) => Ok(len as usize),
+                AioError(e) => Err(e),
+            });
+            if let (Some(Ok(len)), Some(bounce)) = (self.result, self.bounce.take()) {
+                bounce.scatter(len);
+            }
+        }
+        match self.result.unwrap() {
+            Ok(len) => OperationResult::Ok(len),
+            Err(e) => OperationResult::Err(io::Error::from_raw_os_error(e)),
+        }
+    }
+    fn cancel(&mut self, handle: c_int) -> bool {
+        if self.result.is_none() {
+            // whatever `aio_cancel` returns, even an error, the request may still use the
+            // buffer until it's done.
+            unsafe { aio_cancel(handle, &mut self.aiocb) };
+            while aio_error(&self.aiocb) == EINPROGRESS {
+                suspend(&[&self.aiocb], None);
+            }
+        }
+        matches!(self.result(), OperationResult::Err(e) if e.raw_os_error() == Some(ECANCELED))
+    }
+}
+
+/// Blocks until one of the control blocks completes, `timeout` elapses or a signal
+/// interrupts the wait.
+fn suspend(list: &[*const aiocb], timeout: Option<Duration>) {
+    let timeout = timeout.map(|timeout| timespec {
+        // `time_t` may be 32-bit, and 68 years is long enough.
+        tv_sec: timeout.as_secs().min(i32::MAX as u64) as _,
+        tv_nsec: timeout.subsec_nanos() as _,
+    });
+    let timeout = timeout
+        .as_ref()
+        .map_or(null(), |timeout| timeout as *const timespec);
+    unsafe { aio_suspend(list.as_ptr(), list.len() as c_int, timeout) };
+}
+
+/// POSIX AIO has no vectored operations, so several buffers are transferred through a
+/// [`Bounce`] buffer in one request.
+#[derive(Default)]
+pub struct UnixOverlapped {
+    request: Option<Request>,
+}
+
+/// Submits `list` with `lio_listio`, in chunks of at most `AIO_LISTIO_MAX` requests.
+fn listio(list: &mut [*mut aiocb]) -> io::Result<()> {
+    let max = match unsafe { sysconf(_SC_AIO_LISTIO_MAX) } {
+        max if max > 0 => max as usize,
+        _ => list.len().max(1),
+    };
+    for chunk in list.chunks_mut(max) {
+        let len = chunk.len() as c_int;
+        to_operation_result(unsafe { lio_listio(LIO_NOWAIT, chunk.as_ptr(), len, null_mut()) })?;
+    }
+    Ok(())
+}
+
+/// Submits `list` request by request. The notifiers of the requests that are not queued are
+/// released.
+fn submit_each(list: &[*mut aiocb]) -> io::Result<()> {
+    for (i, &aiocb) in list.iter().enumerate() {
+        let f = if unsafe { (*aiocb).aio_lio_opcode } == LIO_WRITE {
+            aio_write
+        } else {
+            aio_read
+        };
+        if let Err(e) = to_operation_result(unsafe { f(aiocb) }) {
+            for &aiocb in &list[i..] {
+                notification::release(unsafe { &*aiocb });
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
 impl AsyncTrait for Unix {
     type Handle = i32;
-    type Overlapped = aiocb;
+    type Overlapped = UnixOverlapped;
     fn overlapped_default() -> Self::Overlapped {
-        unsafe { zeroed() }
+        UnixOverlapped::default()
     }
     fn close(handle: Self::Handle) {
         unsafe { close(handle) };
     }
-    fn cancel(handle: Self::Handle, overlapped: &mut Self::Overlapped) {
-        if unsafe { aio_cancel(handle, overlapped) } != AIO_NOTCANCELED {
-            return;
-        }
-        loop {
-            yield_now();
-            if aio_error(overlapped) != EINPROGRESS {
-                return;
-            }
-        }
+    fn cancel(handle: Self::Handle, overlapped: &mut Self::Overlapped) -> bool {
+        overlapped.request.as_mut().is_some_and(|request| {
+            let canceled = request.cancel(handle);
+            notification::unwatch(&request.aiocb);
+            canceled
+        })
     }
     fn get_result(_handle: Self::Handle, overlapped: &mut Self::Overlapped) -> OperationResult {
-        match aio_error(overlapped) {
-            AioError(0) => OperationResult::Ok(unsafe { aio_return(overlapped) } as usize),
-            EINPROGRESS => OperationResult::Pending,
-            e => OperationResult::Err(io::Error::from_raw_os_error(e.0)),
+        overlapped
+            .request
+            .as_mut()
+            .map_or(OperationResult::Ok(0), Request::result)
+    }
+    fn open(path: &CStr, options: &OpenOptions) -> io::Result<Self::Handle> {
+        let mut oflag = match (options.read, options.is_writable()) {
+            (true, false) => libc::O_RDONLY,
+            (false, _) => libc::O_WRONLY,
+            (true, true) => libc::O_RDWR,
+        };
+        if options.append {
+            oflag |= libc::O_APPEND;
+        }
+        if options.create_new {
+            oflag |= libc::O_CREAT | libc::O_EXCL;
+        } else if options.create {
+            oflag |= libc::O_CREAT;
         }
+        if options.truncate {
+            oflag |= libc::O_TRUNC;
+        }
+        to_result(unsafe { open(path.as_ptr(), oflag, 0o644) })
     }
-    fn open(path: &CStr, create: bool) -> io::Result<Self::Handle> {
-        let oflag = if create {
-            libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC
+    fn submit(submissions: &mut [Submission<Self>]) -> io::Result<()> {
+        let mut list = Vec::new();
+        for s in submissions.iter_mut() {
+            let request = Request::new(s.handle, s.offset, &mut s.buffers);
+            // the control block is boxed in the operation, so it keeps its address.
+            let request = s.overlapped.request.insert(request);
+            notification::init(&mut request.aiocb, s.notifier);
+            list.push(&mut request.aiocb as *mut aiocb);
+        }
+        let result = if notification::LISTIO && list.len() > 1 {
+            listio(&mut list)
         } else {
-            libc::O_RDONLY
+            submit_each(&list)
         };
-        to_result(unsafe { open(path.as_ptr(), oflag, 0o644) })
+        if result.is_err() {
+            // the submitted requests use the buffers, so they are canceled before returning.
+            for s in submissions.iter_mut() {
+                Unix::cancel(s.handle, s.overlapped);
+            }
+        }
+        result
     }
-    fn init_overlapped(
-        handle: Self::Handle,
-        overlapped: &mut Self::Overlapped,
-        offset: u64,
-        buffer: &[u8],
-    ) {
-        *overlapped = unsafe { zeroed() };
-        overlapped.aio_fildes = handle;
-        overlapped.aio_buf = buffer.as_ptr() as *mut _;
-        overlapped.aio_nbytes = buffer.len();
-        overlapped.aio_offset = offset as i64;
-    }
-    fn read(
-        _handle: Self::Handle,
-        overlapped: &mut Self::Overlapped,
-        _buffer: &mut [u8],
-    ) -> io::Result<()> {
-        to_operation_result(unsafe { aio_read(overlapped) })
-    }
-
-    fn write(
-        _handle: Self::Handle,
-        overlapped: &mut Self::Overlapped,
-        _buffer: &[u8],
-    ) -> io::Result<()> {
-        to_operation_result(unsafe { aio_write(overlapped) })
+    fn watch(overlapped: &mut Self::Overlapped, notifier: &Arc<Notifier>) {
+        if let Some(request) = &overlapped.request {
+            notification::watch(&request.aiocb, notifier);
+        }
+    }
+    fn suspend(overlapped: &[&Self::Overlapped], timeout: Option<Duration>) {
+        let list: Vec<_> = overlapped
+            .iter()
+            .filter_map(|o| o.request.as_ref())
+            .filter(|r| r.result.is_none())
+            .map(|r| &r.aiocb as *const aiocb)
+            .collect();
+        if !list.is_empty() {
+            suspend(&list, timeout);
+        }
     }
 }
 
+#[cfg(target_os = "linux")]
+pub type Os = crate::uring::Linux;
+#[cfg(not(target_os = "linux"))]
 pub type Os = Unix;
@@ -0,0 +1,656 @@
+#![cfg(target_os = "linux")]
+#![cfg(not(tarpaulin_include))]
+
+use std::{
+    collections::HashSet,
+    ffi::CStr,
+    io, mem,
+    ptr::null_mut,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Condvar, Mutex, OnceLock,
+    },
+    task::Wake,
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use io_trait::{OpenOptions, OperationResult};
+use libc::{
+    c_int, c_long, c_uint, c_void, mmap, syscall, SYS_io_uring_enter, SYS_io_uring_setup, EAGAIN,
+    EBUSY, ECANCELED, EINTR, EIO, MAP_FAILED, MAP_POPULATE, MAP_SHARED, PROT_READ, PROT_WRITE,
+};
+
+use crate::{
+    async_traits::{AsyncTrait, Notifier, Submission},
+    unix::{Unix, UnixOverlapped},
+};
+
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/io_uring.h
+
+#[repr(C)]
+#[derive(Default)]
+struct io_sqring_offsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct io_cqring_offsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct io_uring_params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: io_sqring_offsets,
+    cq_off: io_cqring_offsets,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct io_uring_sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct io_uring_cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+const IORING_OFF_SQ_RING: i64 = 0;
+const IORING_OFF_CQ_RING: i64 = 0x8000000;
+const IORING_OFF_SQES: i64 = 0x10000000;
+const IORING_ENTER_GETEVENTS: c_uint = 1;
+/// The kernel doesn't access the `iovec` array after submission, Linux 5.5.
+const IORING_FEAT_SUBMIT_STABLE: u32 = 1 << 2;
+const IORING_OP_READV: u8 = 1;
+const IORING_OP_WRITEV: u8 = 2;
+const IORING_OP_ASYNC_CANCEL: u8 = 14;
+
+const ENTRIES: u32 = 256;
+/// The longest time before a failed cancellation is submitted again.
+const RETRY: Duration = Duration::from_millis(10);
+
+fn to_result(result: c_long) -> io::Result<c_long> {
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result)
+}
+
+fn enter(fd: c_int, to_submit: u32, min_complete: u32, flags: c_uint) -> io::Result<c_long> {
+    to_result(unsafe {
+        syscall(
+            SYS_io_uring_enter,
+            fd,
+            to_submit,
+            min_complete,
+            flags,
+            null_mut::<c_void>(),
+            0,
+        )
+    })
+}
+
+fn map(fd: c_int, len: usize, offset: i64) -> io::Result<*mut u8> {
+    let p = unsafe {
+        mmap(
+            null_mut(),
+            len,
+            PROT_READ | PROT_WRITE,
+            MAP_SHARED | MAP_POPULATE,
+            fd,
+            offset,
+        )
+    };
+    if p == MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(p as *mut u8)
+}
+
+/// # Safety
+///
+/// `ring` and `offset` must point to a `u32` field of a mapped ring.
+unsafe fn field(ring: *mut u8, offset: u32) -> *mut u32 {
+    ring.add(offset as usize) as *mut u32
+}
+
+/// # Safety
+///
+/// `p` must be valid and aligned, and only accessed atomically by other threads.
+unsafe fn atomic<'a>(p: *mut u32) -> &'a AtomicU32 {
+    AtomicU32::from_ptr(p)
+}
+
+struct SubmissionQueue {
+    head: *mut u32,
+    tail: *mut u32,
+    mask: u32,
+    array: *mut u32,
+    sqes: *mut io_uring_sqe,
+}
+
+struct CompletionQueue {
+    head: *mut u32,
+    tail: *mut u32,
+    mask: u32,
+    cqes: *const io_uring_cqe,
+}
+
+/// `user_data` of the submitted operations, or the error of `io_uring_enter` if the ring has
+/// failed and all of them have been failed.
+type Pending = Mutex<Result<HashSet<u64>, c_int>>;
+
+/// The number of times completions have been reaped, signaled by the reaping thread, so
+/// entries that don't fit in the rings can be submitted again.
+#[derive(Default)]
+struct Reaped {
+    count: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl Reaped {
+    fn count(&self) -> u64 {
+        *self.count.lock().unwrap()
+    }
+    fn increment(&self) {
+        *self.count.lock().unwrap() += 1;
+        self.changed.notify_all();
+    }
+    /// Blocks until completions are reaped after `count` or `timeout` elapses.
+    fn wait(&self, count: u64, timeout: Duration) {
+        let current = self.count.lock().unwrap();
+        let _ = self
+            .changed
+            .wait_timeout_while(current, timeout, |current| *current == count);
+    }
+}
+
+/// A process-wide ring. Submissions are serialized with a mutex, completions are reaped by
+/// a dedicated thread.
+struct Ring {
+    fd: c_int,
+    sq: Mutex<SubmissionQueue>,
+    pending: Arc<Pending>,
+    reaped: Arc<Reaped>,
+}
+
+// SAFETY: the rings are mapped for the lifetime of the process, the submission queue is
+// guarded by a mutex and the completion queue is owned by the reaping thread.
+unsafe impl Send for SubmissionQueue {}
+unsafe impl Send for CompletionQueue {}
+
+impl Ring {
+    fn new() -> io::Result<(Self, CompletionQueue)> {
+        let mut p = io_uring_params::default();
+        let fd = to_result(unsafe { syscall(SYS_io_uring_setup, ENTRIES, &mut p) })? as c_int;
+        if p.features & IORING_FEAT_SUBMIT_STABLE == 0 {
+            unsafe { libc::close(fd) };
+            return Err(io::Error::from(io::ErrorKind::Unsupported));
+        }
+        let sq_len = p.sq_off.array as usize + p.sq_entries as usize * size_of::<u32>();
+        let cq_len = p.cq_off.cqes as usize + p.cq_entries as usize * size_of::<io_uring_cqe>();
+        let sqes_len = p.sq_entries as usize * size_of::<io_uring_sqe>();
+        let result = (|| {
+            let sq = map(fd, sq_len, IORING_OFF_SQ_RING)?;
+            let cq = map(fd, cq_len, IORING_OFF_CQ_RING)?;
+            let sqes = map(fd, sqes_len, IORING_OFF_SQES)?;
+            unsafe {
+                Ok((
+                    SubmissionQueue {
+                        head: field(sq, p.sq_off.head),
+                        tail: field(sq, p.sq_off.tail),
+                        mask: *field(sq, p.sq_off.ring_mask),
+                        array: field(sq, p.sq_off.array),
+                        sqes: sqes as *mut io_uring_sqe,
+                    },
+                    CompletionQueue {
+                        head: field(cq, p.cq_off.head),
+                        tail: field(cq, p.cq_off.tail),
+                        mask: *field(cq, p.cq_off.ring_mask),
+                        cqes: cq.add(p.cq_off.cqes as usize) as *const io_uring_cqe,
+                    },
+                ))
+            }
+        })();
+        match result {
+            Ok((sq, cq)) => Ok((
+                Self {
+                    fd,
+                    sq: Mutex::new(sq),
+                    pending: Arc::new(Mutex::new(Ok(HashSet::new()))),
+                    reaped: Arc::default(),
+                },
+                cq,
+            )),
+            Err(e) => {
+                unsafe { libc::close(fd) };
+                Err(e)
+            }
+        }
+    }
+    /// Submits entries in chunks of the queue size. Returns the number of entries consumed by
+    /// the kernel, the other entries are not submitted.
+    fn submit(&self, sqes: &[io_uring_sqe]) -> (usize, io::Result<()>) {
+        // the reaping thread can't complete the entries before they are added.
+        let mut pending = self.pending.lock().unwrap();
+        let pending = match &mut *pending {
+            Ok(pending) => pending,
+            Err(e) => return (0, Err(io::Error::from_raw_os_error(*e))),
+        };
+        let user_data = sqes.iter().map(|sqe| sqe.user_data).filter(|&u| u != 0);
+        pending.extend(user_data);
+        let (consumed, result) = self.submit_locked(sqes);
+        for sqe in &sqes[consumed..] {
+            pending.remove(&sqe.user_data);
+        }
+        (consumed, result)
+    }
+    /// Submits entries that are already added to the pending ones.
+    fn submit_locked(&self, sqes: &[io_uring_sqe]) -> (usize, io::Result<()>) {
+        let sq = self.sq.lock().unwrap();
+        let mut consumed = 0;
+        for chunk in sqes.chunks(sq.mask as usize + 1) {
+            let len = chunk.len() as u32;
+            unsafe {
+                // the kernel consumes all entries during `io_uring_enter`, so the queue is empty.
+                let tail = *sq.tail;
+                for (i, sqe) in chunk.iter().enumerate() {
+                    let index = tail.wrapping_add(i as u32) & sq.mask;
+                    sq.sqes.add(index as usize).write(*sqe);
+                    *sq.array.add(index as usize) = index;
+                }
+                atomic(sq.tail).store(tail.wrapping_add(len), Ordering::Release);
+                let result = enter(self.fd, len, 0, 0);
+                let head = atomic(sq.head).load(Ordering::Acquire);
+                consumed += head.wrapping_sub(tail) as usize;
+                if head != tail.wrapping_add(len) {
+                    // the entries haven't been consumed, so it's safe to take them back.
+                    atomic(sq.tail).store(head, Ordering::Release);
+                    let e = result
+                        .err()
+                        .unwrap_or_else(|| io::ErrorKind::WouldBlock.into());
+                    return (consumed, Err(e));
+                }
+            }
+        }
+        (consumed, Ok(()))
+    }
+}
+
+/// Waits for completions and passes them to their operations. If waiting fails for another
+/// reason than an interruption or a full completion queue, the pending operations fail with
+/// the error, because their completions can't be reaped anymore.
+fn reap(fd: c_int, cq: CompletionQueue, pending: &Pending, reaped: &Reaped) {
+    loop {
+        let error = match enter(fd, 0, 1, IORING_ENTER_GETEVENTS) {
+            Ok(_) => None,
+            Err(e) => match e.raw_os_error() {
+                Some(EINTR | EAGAIN | EBUSY) => None,
+                e => Some(e.unwrap_or(EIO)),
+            },
+        };
+        unsafe {
+            let mut head = *cq.head;
+            let tail = atomic(cq.tail).load(Ordering::Acquire);
+            while head != tail {
+                let cqe = &*cq.cqes.add((head & cq.mask) as usize);
+                if cqe.user_data != 0 {
+                    if let Ok(pending) = &mut *pending.lock().unwrap() {
+                        pending.remove(&cqe.user_data);
+                    }
+                    // SAFETY: created by `Arc::into_raw` in `Uring::submit`.
+                    Arc::from_raw(cqe.user_data as *const Completion).complete(cqe.res);
+                }
+                head = head.wrapping_add(1);
+            }
+            atomic(cq.head).store(head, Ordering::Release);
+        }
+        reaped.increment();
+        if let Some(error) = error {
+            let failed = mem::replace(&mut *pending.lock().unwrap(), Err(error));
+            for user_data in failed.into_iter().flatten() {
+                // SAFETY: created by `Arc::into_raw` in `Uring::submit` and not reaped.
+                unsafe { Arc::from_raw(user_data as *const Completion) }.complete(-error);
+            }
+            return;
+        }
+    }
+}
+
+/// Returns the ring or `None` if io_uring isn't available.
+fn ring() -> Option<&'static Ring> {
+    static RING: OnceLock<Option<Ring>> = OnceLock::new();
+    RING.get_or_init(|| {
+        let (ring, cq) = Ring::new().ok()?;
+        let fd = ring.fd;
+        let pending = ring.pending.clone();
+        let reaped = ring.reaped.clone();
+        thread::Builder::new()
+            .name("io_uring".to_string())
+            .spawn(move || reap(fd, cq, &pending, &reaped))
+            .ok()?;
+        Some(ring)
+    })
+    .as_ref()
+}
+
+pub fn is_available() -> bool {
+    ring().is_some()
+}
+
+/// The result of one operation, shared with the reaping thread.
+struct Completion {
+    result: Mutex<Option<i32>>,
+    done: Condvar,
+    notifier: Arc<Notifier>,
+}
+
+impl Completion {
+    fn complete(&self, res: i32) {
+        *self.result.lock().unwrap() = Some(res);
+        self.done.notify_all();
+        self.notifier.notify();
+    }
+    fn wait(&self) {
+        let mut result = self.result.lock().unwrap();
+        while result.is_none() {
+            result = self.done.wait(result).unwrap();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct UringOverlapped(Option<Arc<Completion>>);
+
+pub struct Uring();
+
+/// Unparks a thread blocked in [`Uring::suspend`].
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+impl AsyncTrait for Uring {
+    type Handle = c_int;
+    type Overlapped = UringOverlapped;
+    fn overlapped_default() -> Self::Overlapped {
+        UringOverlapped::default()
+    }
+    fn close(handle: Self::Handle) {
+        Unix::close(handle)
+    }
+    fn cancel(_handle: Self::Handle, overlapped: &mut Self::Overlapped) -> bool {
+        let Some(completion) = &overlapped.0 else {
+            return false;
+        };
+        if completion.result.lock().unwrap().is_none() {
+            if let Some(ring) = ring() {
+                let sqe = io_uring_sqe {
+                    opcode: IORING_OP_ASYNC_CANCEL,
+                    fd: -1,
+                    addr: Arc::as_ptr(completion) as u64,
+                    ..Default::default()
+                };
+                // the submission is retried until the operation is done, so a full queue or an
+                // interruption doesn't leave it running. Reaping makes room, and the timeout
+                // covers other errors. If the ring fails, the operation fails.
+                loop {
+                    let reaped = ring.reaped.count();
+                    if ring.submit(&[sqe]).1.is_ok() || completion.result.lock().unwrap().is_some()
+                    {
+                        break;
+                    }
+                    ring.reaped.wait(reaped, RETRY);
+                }
+            }
+            // the buffers can't be released until the kernel is done with them.
+            completion.wait();
+        }
+        // an operation that is already running is interrupted.
+        let result = *completion.result.lock().unwrap();
+        matches!(result, Some(res) if res == -ECANCELED || res == -EINTR)
+    }
+    fn get_result(_handle: Self::Handle, overlapped: &mut Self::Overlapped) -> OperationResult {
+        let result = overlapped
+            .0
+            .as_ref()
+            .and_then(|c| *c.result.lock().unwrap());
+        match result {
+            None => OperationResult::Pending,
+            Some(res) if res < 0 => OperationResult::Err(io::Error::from_raw_os_error(-res)),
+            Some(res) => OperationResult::Ok(res as usize),
+        }
+    }
+    fn open(path: &CStr, options: &OpenOptions) -> io::Result<Self::Handle> {
+        Unix::open(path, options)
+    }
+    fn submit(submissions: &mut [Submission<Self>]) -> io::Result<()> {
+        let ring = ring().ok_or_else(|| io::Error::from(io::ErrorKind::Unsupported))?;
+        let completions: Vec<_> = submissions
+            .iter()
+            .map(|s| {
+                Arc::new(Completion {
+                    result: Mutex::default(),
+                    done: Condvar::new(),
+                    notifier: s.notifier.clone(),
+                })
+            })
+            .collect();
+        let sqes: Vec<_> = submissions
+            .iter()
+            .zip(&completions)
+            .map(|(s, completion)| io_uring_sqe {
+                opcode: if s.buffers.is_write() {
+                    IORING_OP_WRITEV
+                } else {
+                    IORING_OP_READV
+                },
+                fd: s.handle,
+                off: s.offset,
+                addr: s.buffers.as_ptr() as u64,
+                len: s.buffers.len() as u32,
+                user_data: Arc::into_raw(completion.clone()) as u64,
+                ..Default::default()
+            })
+            .collect();
+        let (consumed, result) = ring.submit(&sqes);
+        for sqe in &sqes[consumed..] {
+            // SAFETY: created by `Arc::into_raw` above and never passed to the kernel.
+            drop(unsafe { Arc::from_raw(sqe.user_data as *const Completion) });
+        }
+        for (s, completion) in submissions.iter_mut().zip(completions).take(consumed) {
+            s.overlapped.0 = Some(completion);
+        }
+        if result.is_err() {
+            for s in &mut submissions[..consumed] {
+                Self::cancel(s.handle, s.overlapped);
+            }
+        }
+        result
+    }
+    fn suspend(overlapped: &[&Self::Overlapped], timeout: Option<Duration>) {
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let completions: Vec<_> = overlapped.iter().filter_map(|o| o.0.as_ref()).collect();
+        // the wakers of the operations, e.g. of a pending future, are registered again or woken
+        // afterwards.
+        let wakers: Vec<_> = completions
+            .iter()
+            .map(|completion| completion.notifier.replace(&waker))
+            .collect();
+        // the results are checked after the registration, so a completion isn't missed.
+        if completions
+            .iter()
+            .all(|completion| completion.result.lock().unwrap().is_none())
+        {
+            match timeout {
+                Some(timeout) => thread::park_timeout(timeout),
+                None => thread::park(),
+            }
+        }
+        for (completion, waker) in completions.iter().zip(wakers) {
+            completion.notifier.restore(waker);
+        }
+    }
+}
+
+pub enum LinuxOverlapped {
+    Uring(UringOverlapped),
+    Aio(Box<UnixOverlapped>),
+}
+
+/// Uses io_uring if the kernel supports it and POSIX AIO otherwise.
+pub struct Linux();
+
+impl AsyncTrait for Linux {
+    type Handle = c_int;
+    type Overlapped = LinuxOverlapped;
+    fn overlapped_default() -> Self::Overlapped {
+        if is_available() {
+            LinuxOverlapped::Uring(Uring::overlapped_default())
+        } else {
+            LinuxOverlapped::Aio(Box::default())
+        }
+    }
+    fn close(handle: Self::Handle) {
+        Unix::close(handle)
+    }
+    fn cancel(handle: Self::Handle, overlapped: &mut Self::Overlapped) -> bool {
+        match overlapped {
+            LinuxOverlapped::Uring(o) => Uring::cancel(handle, o),
+            LinuxOverlapped::Aio(o) => Unix::cancel(handle, o),
+        }
+    }
+    fn get_result(handle: Self::Handle, overlapped: &mut Self::Overlapped) -> OperationResult {
+        match overlapped {
+            LinuxOverlapped::Uring(o) => Uring::get_result(handle, o),
+            LinuxOverlapped::Aio(o) => Unix::get_result(handle, o),
+        }
+    }
+    fn open(path: &CStr, options: &OpenOptions) -> io::Result<Self::Handle> {
+        Unix::open(path, options)
+    }
+    // all control blocks are created for the same backend, see `overlapped_default`.
+    fn submit(submissions: &mut [Submission<Self>]) -> io::Result<()> {
+        if is_available() {
+            let mut submissions: Vec<_> = submissions
+                .iter_mut()
+                .map(|s| {
+                    s.map(|o| match o {
+                        LinuxOverlapped::Uring(o) => o,
+                        LinuxOverlapped::Aio(_) => unreachable!(),
+                    })
+                })
+                .collect();
+            Uring::submit(&mut submissions)
+        } else {
+            let mut submissions: Vec<_> = submissions
+                .iter_mut()
+                .map(|s| {
+                    s.map(|o| match o {
+                        LinuxOverlapped::Aio(o) => &mut **o,
+                        LinuxOverlapped::Uring(_) => unreachable!(),
+                    })
+                })
+                .collect();
+            Unix::submit(&mut submissions)
+        }
+    }
+    fn watch(overlapped: &mut Self::Overlapped, notifier: &Arc<Notifier>) {
+        match overlapped {
+            LinuxOverlapped::Uring(o) => Uring::watch(o, notifier),
+            LinuxOverlapped::Aio(o) => Unix::watch(o, notifier),
+        }
+    }
+    fn suspend(overlapped: &[&Self::Overlapped], timeout: Option<Duration>) {
+        if is_available() {
+            let overlapped: Vec<_> = overlapped
+                .iter()
+                .filter_map(|o| match o {
+                    LinuxOverlapped::Uring(o) => Some(o),
+                    LinuxOverlapped::Aio(_) => None,
+                })
+                .collect();
+            Uring::suspend(&overlapped, timeout)
+        } else {
+            let overlapped: Vec<_> = overlapped
+                .iter()
+                .filter_map(|o| match o {
+                    LinuxOverlapped::Aio(o) => Some(&**o),
+                    LinuxOverlapped::Uring(_) => None,
+                })
+                .collect();
+            Unix::suspend(&overlapped, timeout)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_available, Uring};
+    use crate::{
+        async_io::test::{cancel, round_trip, timeout},
+        unix::Unix,
+    };
+
+    #[test]
+    fn test_uring() {
+        if !is_available() {
+            return;
+        }
+        round_trip::<Uring>("_test_uring.txt");
+        timeout::<Uring>("_test_uring.fifo");
+        cancel::<Uring>("_test_uring_cancel.fifo");
+    }
+
+    #[test]
+    fn test_aio_fallback() {
+        round_trip::<Unix>("_test_aio_fallback.txt");
+        timeout::<Unix>("_test_aio_fallback.fifo");
+        cancel::<Unix>("_test_aio_fallback_cancel.fifo");
+    }
+}
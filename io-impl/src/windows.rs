@@ -1,20 +1,43 @@
 #![cfg(target_family = "windows")]
 #![cfg(not(tarpaulin_include))]
-use std::{ffi::CStr, io, os::windows::raw::HANDLE, ptr::null_mut};
+use std::{
+    ffi::CStr,
+    io,
+    os::windows::raw::HANDLE,
+    ptr::{null, null_mut},
+    sync::Arc,
+    time::Duration,
+};
 
-use io_trait::OperationResult;
+use io_trait::{OpenOptions, OperationResult};
 
 use crate::{
-    async_traits::AsyncTrait,
+    async_traits::{submit_each, AsyncTrait, Bounce, Buffers, Notifier, Submission},
     windows_api::{
-        self, CancelIoEx, CloseHandle, CreateFileA, Error, GetLastError, GetOverlappedResult,
-        ReadFile, WriteFile, BOOL, CREATE_ALWAYS, DWORD, ERROR_SUCCESS, FILE_FLAG_OVERLAPPED,
-        GENERIC_READ, GENERIC_WRITE, LPCVOID, LPVOID, OPEN_ALWAYS, OVERLAPPED,
+        self, CancelIoEx, CloseHandle, CreateEventA, CreateFileA, Error, GetLastError,
+        GetOverlappedResult, GetOverlappedResultEx, ReadFile, RegisterWaitForSingleObject,
+        UnregisterWaitEx, WaitForMultipleObjects, WriteFile, BOOL, BOOLEAN, CREATE_ALWAYS,
+        CREATE_NEW, DWORD, ERROR_SUCCESS, FALSE, FILE_FLAG_OVERLAPPED, FILE_GENERIC_APPEND,
+        GENERIC_READ, GENERIC_WRITE, INFINITE, INVALID_HANDLE_VALUE, LPCVOID, LPVOID,
+        MAXIMUM_WAIT_OBJECTS, OPEN_ALWAYS, OPEN_EXISTING, OVERLAPPED, PVOID, TRUE,
+        TRUNCATE_EXISTING, WT_EXECUTEONLYONCE,
     },
 };
 
 pub struct Windows();
 
+/// `WaitForMultipleObjects` waits for at most `MAXIMUM_WAIT_OBJECTS` events, so the other
+/// requests are checked again after at most this time.
+const RESCAN: Duration = Duration::from_millis(10);
+
+/// Returns the timeout in milliseconds, rounded up, so a wait doesn't end before it elapses.
+fn to_ms(timeout: Option<Duration>) -> DWORD {
+    timeout.map_or(INFINITE, |timeout| {
+        let ms = timeout.as_nanos().div_ceil(1_000_000);
+        ms.min(INFINITE as u128 - 1) as DWORD
+    })
+}
+
 fn get_overlapped_result(handle: HANDLE, overlapped: &mut OVERLAPPED, wait: bool) -> (BOOL, DWORD) {
     let mut size: DWORD = 0;
     let result = unsafe { GetOverlappedResult(handle, overlapped, &mut size, wait.into()) };
@@ -29,7 +52,9 @@ fn get_last_error(v: BOOL) -> Error {
 }
 
 fn is_pending(e: Error) -> bool {
-    e == windows_api::ERROR_IO_PENDING || e == windows_api::ERROR_IO_INCOMPLETE
+    e == windows_api::ERROR_IO_PENDING
+        || e == windows_api::ERROR_IO_INCOMPLETE
+        || e == windows_api::WAIT_TIMEOUT
 }
 
 fn to_operation_result((v, size): (BOOL, DWORD)) -> OperationResult {
@@ -54,29 +79,217 @@ fn to_result(result: BOOL) -> io::Result<()> {
     Err(e.to_error())
 }
 
+extern "system" fn notify(context: PVOID, _timer_or_wait_fired: BOOLEAN) {
+    // SAFETY: the notifier is released after the wait is unregistered, see `Request::drop`.
+    unsafe { &*(context as *const Notifier) }.notify();
+}
+
+/// The request of an operation.
+pub struct Request {
+    overlapped: OVERLAPPED,
+    /// It's released when the request completes.
+    bounce: Option<Bounce>,
+    /// A wait on the event that notifies the notifier, see `Request::watch`.
+    wait: Option<(HANDLE, *const Notifier)>,
+}
+
+impl Request {
+    fn new(offset: u64, bounce: Option<Bounce>) -> io::Result<Self> {
+        // each request has its own event, because the file handle is signaled by any operation
+        // on the file.
+        let event = unsafe { CreateEventA(null_mut(), TRUE, FALSE, null()) };
+        if event.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            overlapped: OVERLAPPED::new(offset, event),
+            bounce,
+            wait: None,
+        })
+    }
+    /// Notifies `notifier` from a thread pool when the event is signaled.
+    fn watch(&mut self, notifier: &Arc<Notifier>) {
+        if self.wait.is_some() {
+            return;
+        }
+        let context = Arc::into_raw(notifier.clone());
+        let mut wait = null_mut();
+        let result = unsafe {
+            RegisterWaitForSingleObject(
+                &mut wait,
+                self.overlapped.hEvent,
+                notify,
+                context as PVOID,
+                INFINITE,
+                WT_EXECUTEONLYONCE,
+            )
+        };
+        if result.to_bool() {
+            self.wait = Some((wait, context));
+        } else {
+            drop(unsafe { Arc::from_raw(context) });
+            // the operation is polled again.
+            notifier.notify();
+        }
+    }
+    /// Copies the data of a completed read to the read buffers once.
+    fn complete(&mut self, result: OperationResult) -> OperationResult {
+        if !matches!(result, OperationResult::Pending) {
+            if let (OperationResult::Ok(len), Some(bounce)) = (&result, self.bounce.take()) {
+                bounce.scatter(*len);
+            }
+        }
+        result
+    }
+    fn result(&mut self, handle: HANDLE) -> OperationResult {
+        let result =
+            to_operation_result(get_overlapped_result(handle, &mut self.overlapped, false));
+        self.complete(result)
+    }
+    fn wait(&mut self, handle: HANDLE, timeout: Option<Duration>) -> OperationResult {
+        let mut size: DWORD = 0;
+        let ms = to_ms(timeout);
+        let result =
+            unsafe { GetOverlappedResultEx(handle, &mut self.overlapped, &mut size, ms, FALSE) };
+        self.complete(to_operation_result((result, size)))
+    }
+    fn cancel(&mut self, handle: HANDLE) -> bool {
+        unsafe { CancelIoEx(handle, &mut self.overlapped) };
+        // the request has its own event, so the wait isn't woken by other operations.
+        let result = get_overlapped_result(handle, &mut self.overlapped, true);
+        get_last_error(result.0) == windows_api::ERROR_OPERATION_ABORTED
+    }
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        if let Some((wait, context)) = self.wait {
+            // blocks until a running callback returns.
+            unsafe { UnregisterWaitEx(wait, INVALID_HANDLE_VALUE) };
+            drop(unsafe { Arc::from_raw(context) });
+        }
+        unsafe { CloseHandle(self.overlapped.hEvent) };
+    }
+}
+
+/// Overlapped I/O has no general vectored operations, `WriteFileGather` needs page-sized
+/// buffers, so several buffers are transferred through a [`Bounce`] buffer in one request.
+pub type WindowsOverlapped = Option<Request>;
+
+fn submit(
+    handle: HANDLE,
+    overlapped: &mut WindowsOverlapped,
+    offset: u64,
+    buffers: &mut Buffers,
+) -> io::Result<()> {
+    let bounce = Bounce::new(buffers);
+    let buffer = Bounce::buffer(&bounce, buffers);
+    let (p, len) = (buffer.as_ptr(), buffer.len() as DWORD);
+    // the control block is boxed in the operation, so it keeps its address.
+    let request = overlapped.insert(Request::new(offset, bounce)?);
+    let result = unsafe {
+        if buffers.is_write() {
+            WriteFile(
+                handle,
+                p as LPCVOID,
+                len,
+                null_mut(),
+                &mut request.overlapped,
+            )
+        } else {
+            ReadFile(
+                handle,
+                p as LPVOID,
+                len,
+                null_mut(),
+                &mut request.overlapped,
+            )
+        }
+    };
+    // a request that hasn't been submitted doesn't use the buffers.
+    to_result(result).inspect_err(|_| *overlapped = None)
+}
+
 impl AsyncTrait for Windows {
     type Handle = HANDLE;
-    type Overlapped = OVERLAPPED;
+    type Overlapped = WindowsOverlapped;
     fn overlapped_default() -> Self::Overlapped {
-        OVERLAPPED::default()
+        None
     }
     fn close(handle: Self::Handle) {
         unsafe { CloseHandle(handle) };
     }
-    fn cancel(handle: Self::Handle, overlapped: &mut Self::Overlapped) {
-        if unsafe { CancelIoEx(handle, overlapped) }.to_bool() {
-            return;
-        }
-        let _ = get_overlapped_result(handle, overlapped, true);
+    fn cancel(handle: Self::Handle, overlapped: &mut Self::Overlapped) -> bool {
+        overlapped
+            .as_mut()
+            .is_some_and(|request| request.cancel(handle))
     }
     fn get_result(handle: Self::Handle, overlapped: &mut Self::Overlapped) -> OperationResult {
-        to_operation_result(get_overlapped_result(handle, overlapped, false))
+        overlapped
+            .as_mut()
+            .map_or(OperationResult::Ok(0), |request| request.result(handle))
+    }
+    fn watch(overlapped: &mut Self::Overlapped, notifier: &Arc<Notifier>) {
+        if let Some(request) = overlapped {
+            request.watch(notifier);
+        }
+    }
+    fn wait(
+        handle: Self::Handle,
+        overlapped: &mut Self::Overlapped,
+        timeout: Option<Duration>,
+    ) -> OperationResult {
+        overlapped
+            .as_mut()
+            .map_or(OperationResult::Ok(0), |request| {
+                request.wait(handle, timeout)
+            })
+    }
+    /// Waits for the events of the requests, which stay signaled once they complete.
+    fn suspend(overlapped: &[&Self::Overlapped], timeout: Option<Duration>) {
+        let events: Vec<_> = overlapped
+            .iter()
+            .filter_map(|o| o.as_ref())
+            .map(|request| request.overlapped.hEvent)
+            .collect();
+        if events.is_empty() {
+            return;
+        }
+        let (events, timeout) = if events.len() > MAXIMUM_WAIT_OBJECTS {
+            let timeout = timeout.map_or(RESCAN, |timeout| timeout.min(RESCAN));
+            (&events[..MAXIMUM_WAIT_OBJECTS], Some(timeout))
+        } else {
+            (&events[..], timeout)
+        };
+        unsafe {
+            WaitForMultipleObjects(
+                events.len() as DWORD,
+                events.as_ptr(),
+                FALSE,
+                to_ms(timeout),
+            )
+        };
     }
-    fn open(path: &CStr, create: bool) -> io::Result<Self::Handle> {
-        let (da, cp) = if create {
-            (GENERIC_WRITE, CREATE_ALWAYS)
+    fn open(path: &CStr, options: &OpenOptions) -> io::Result<Self::Handle> {
+        // the same mapping as `std::fs::OpenOptions` on Windows.
+        let write = if options.append {
+            Some(FILE_GENERIC_APPEND)
+        } else if options.write {
+            Some(GENERIC_WRITE)
         } else {
-            (GENERIC_READ, OPEN_ALWAYS)
+            None
+        };
+        let da = match (options.read, write) {
+            (true, Some(write)) => GENERIC_READ | write,
+            (false, Some(write)) => write,
+            (_, None) => GENERIC_READ,
+        };
+        let cp = match (options.create, options.truncate, options.create_new) {
+            (_, _, true) => CREATE_NEW,
+            (true, false, false) => OPEN_ALWAYS,
+            (true, true, false) => CREATE_ALWAYS,
+            (false, false, false) => OPEN_EXISTING,
+            (false, true, false) => TRUNCATE_EXISTING,
         };
         match unsafe {
             CreateFileA(
@@ -93,43 +306,9 @@ impl AsyncTrait for Windows {
             h => Ok(h),
         }
     }
-    fn init_overlapped(
-        _handle: Self::Handle,
-        overlapped: &mut Self::Overlapped,
-        offset: u64,
-        _buffer: &[u8],
-    ) {
-        *overlapped = OVERLAPPED::new(offset);
-    }
-
-    fn read(
-        handle: Self::Handle,
-        overlapped: &mut Self::Overlapped,
-        buffer: &mut [u8],
-    ) -> io::Result<()> {
-        to_result(unsafe {
-            ReadFile(
-                handle,
-                buffer.as_mut_ptr() as LPVOID,
-                buffer.len() as DWORD,
-                null_mut(),
-                overlapped,
-            )
-        })
-    }
-    fn write(
-        handle: Self::Handle,
-        overlapped: &mut Self::Overlapped,
-        buffer: &[u8],
-    ) -> io::Result<()> {
-        to_result(unsafe {
-            WriteFile(
-                handle,
-                buffer.as_ptr() as LPCVOID,
-                buffer.len() as DWORD,
-                null_mut(),
-                overlapped,
-            )
+    fn submit(submissions: &mut [Submission<Self>]) -> io::Result<()> {
+        submit_each(submissions, |s| {
+            submit(s.handle, s.overlapped, s.offset, &mut s.buffers)
         })
     }
 }
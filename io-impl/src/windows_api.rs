@@ -6,6 +6,7 @@
 
 use std::{
     io,
+    ops::BitOr,
     os::{raw::c_void, windows::raw::HANDLE},
     ptr::null_mut,
 };
@@ -22,7 +23,7 @@ pub type LPDWORD = *mut DWORD;
 // https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-dtyp/c0b7741b-f577-4eed-aff3-2e909df10a4d
 pub type LPVOID = *mut c_void;
 pub type LPCVOID = *const c_void;
-type PVOID = *mut c_void;
+pub type PVOID = *mut c_void;
 
 // https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-dtyp/21eec394-630d-49ed-8b4a-ab74a1614611
 type ULONG_PTR = usize;
@@ -68,17 +69,19 @@ pub struct OVERLAPPED {
     Internal: ULONG_PTR,
     InternalHigh: ULONG_PTR,
     pub OffsetOrPointer: OVERLAPPED_OffsetOrPointer,
-    hEvent: HANDLE,
+    pub hEvent: HANDLE,
 }
 
 impl Default for OVERLAPPED {
     fn default() -> Self {
-        Self::new(0)
+        Self::new(0, null_mut())
     }
 }
 
 impl OVERLAPPED {
-    pub fn new(offset: u64) -> Self {
+    /// `event` is signaled when the operation completes. If it's null, the file handle is
+    /// signaled instead.
+    pub fn new(offset: u64, event: HANDLE) -> Self {
         Self {
             Internal: 0,
             InternalHigh: 0,
@@ -88,7 +91,7 @@ impl OVERLAPPED {
                     OffsetHigh: (offset >> 32) as DWORD,
                 },
             },
-            hEvent: null_mut(),
+            hEvent: event,
         }
     }
 }
@@ -96,10 +99,27 @@ impl OVERLAPPED {
 pub type LPOVERLAPPED = *mut OVERLAPPED;
 
 // https://learn.microsoft.com/en-us/windows/win32/secauthz/access-mask
+#[derive(Clone, Copy)]
 #[repr(transparent)]
 pub struct ACCESS_MASK(DWORD);
 pub const GENERIC_READ: ACCESS_MASK = ACCESS_MASK(0x80000000);
 pub const GENERIC_WRITE: ACCESS_MASK = ACCESS_MASK(0x40000000);
+// https://learn.microsoft.com/en-us/windows/win32/fileio/file-access-rights-constants
+/// `FILE_GENERIC_WRITE` without `FILE_WRITE_DATA`, so data can only be appended.
+pub const FILE_GENERIC_APPEND: ACCESS_MASK = ACCESS_MASK(
+    0x00020000 // STANDARD_RIGHTS_WRITE
+    | 0x00100000 // SYNCHRONIZE
+    | 0x0100 // FILE_WRITE_ATTRIBUTES
+    | 0x0010 // FILE_WRITE_EA
+    | 0x0004, // FILE_APPEND_DATA
+);
+
+impl BitOr for ACCESS_MASK {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
 
 // https://learn.microsoft.com/en-us/windows/win32/api/wtypesbase/ns-wtypesbase-security_attributes
 #[repr(C)]
@@ -112,8 +132,11 @@ type LPSECURITY_ATTRIBUTES = *mut SECURITY_ATTRIBUTES;
 
 #[repr(transparent)]
 pub struct CreationDisposition(DWORD);
+pub const CREATE_NEW: CreationDisposition = CreationDisposition(1);
 pub const CREATE_ALWAYS: CreationDisposition = CreationDisposition(2);
+pub const OPEN_EXISTING: CreationDisposition = CreationDisposition(3);
 pub const OPEN_ALWAYS: CreationDisposition = CreationDisposition(4);
+pub const TRUNCATE_EXISTING: CreationDisposition = CreationDisposition(5);
 
 #[repr(transparent)]
 pub struct FlagsAndAttributes(DWORD);
@@ -125,8 +148,11 @@ pub const FILE_FLAG_OVERLAPPED: FlagsAndAttributes = FlagsAndAttributes(0x400000
 pub struct Error(DWORD);
 pub const ERROR_SUCCESS: Error = Error(0);
 pub const ERROR_HANDLE_EOF: Error = Error(38);
+pub const ERROR_OPERATION_ABORTED: Error = Error(995);
 pub const ERROR_IO_INCOMPLETE: Error = Error(996);
 pub const ERROR_IO_PENDING: Error = Error(997);
+// https://learn.microsoft.com/en-us/windows/win32/debug/system-error-codes--0-499-
+pub const WAIT_TIMEOUT: Error = Error(258);
 impl Error {
     pub fn to_error(self) -> io::Error {
         io::Error::from_raw_os_error(self.0 as i32)
@@ -198,8 +224,87 @@ extern "system" {
     ) -> BOOL;
 }
 
+// https://learn.microsoft.com/en-us/windows/win32/api/ioapiset/nf-ioapiset-getoverlappedresultex
+pub const INFINITE: DWORD = 0xFFFFFFFF;
+#[link(name = "kernel32")]
+extern "system" {
+    pub fn GetOverlappedResultEx(
+        hFile: HANDLE,                       // [in]
+        lpOverlapped: LPOVERLAPPED,          // [in]
+        lpNumberOfBytesTransferred: LPDWORD, // [out]
+        dwMilliseconds: DWORD,               // [in]
+        bAlertable: BOOL,                    // [in]
+    ) -> BOOL;
+}
+
+// https://learn.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-createeventa
+#[link(name = "kernel32")]
+extern "system" {
+    pub fn CreateEventA(
+        lpEventAttributes: LPSECURITY_ATTRIBUTES, // [in, optional]
+        bManualReset: BOOL,                       // [in]
+        bInitialState: BOOL,                      // [in]
+        lpName: LPCSTR,                           // [in, optional]
+    ) -> HANDLE;
+}
+
 // https://learn.microsoft.com/en-us/windows/win32/api/errhandlingapi/nf-errhandlingapi-getlasterror
 #[link(name = "kernel32")]
 extern "system" {
     pub fn GetLastError() -> Error;
 }
+
+// https://learn.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-registerwaitforsingleobject
+pub type BOOLEAN = u8;
+pub type WAITORTIMERCALLBACK = extern "system" fn(PVOID, BOOLEAN);
+pub const WT_EXECUTEONLYONCE: DWORD = 0x00000008;
+#[link(name = "kernel32")]
+extern "system" {
+    pub fn RegisterWaitForSingleObject(
+        phNewWaitObject: *mut HANDLE,  // [out]
+        hObject: HANDLE,               // [in]
+        Callback: WAITORTIMERCALLBACK, // [in]
+        Context: PVOID,                // [in, optional]
+        dwMilliseconds: DWORD,         // [in]
+        dwFlags: DWORD,                // [in]
+    ) -> BOOL;
+}
+
+// https://learn.microsoft.com/en-us/windows/win32/sync/unregisterwaitex
+#[link(name = "kernel32")]
+extern "system" {
+    pub fn UnregisterWaitEx(
+        WaitHandle: HANDLE,      // [in]
+        CompletionEvent: HANDLE, // [in, optional]
+    ) -> BOOL;
+}
+
+// https://learn.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-waitformultipleobjects
+pub const MAXIMUM_WAIT_OBJECTS: usize = 64;
+#[link(name = "kernel32")]
+extern "system" {
+    pub fn WaitForMultipleObjects(
+        nCount: DWORD,            // [in]
+        lpHandles: *const HANDLE, // [in]
+        bWaitAll: BOOL,           // [in]
+        dwMilliseconds: DWORD,    // [in]
+    ) -> DWORD;
+}
+
+// https://learn.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-createnamedpipea
+#[cfg(test)]
+pub const PIPE_ACCESS_DUPLEX: DWORD = 0x00000003;
+#[cfg(test)]
+#[link(name = "kernel32")]
+extern "system" {
+    pub fn CreateNamedPipeA(
+        lpName: LPCSTR,                              // [in]
+        dwOpenMode: DWORD,                           // [in]
+        dwPipeMode: DWORD,                           // [in]
+        nMaxInstances: DWORD,                        // [in]
+        nOutBufferSize: DWORD,                       // [in]
+        nInBufferSize: DWORD,                        // [in]
+        nDefaultTimeOut: DWORD,                      // [in]
+        lpSecurityAttributes: LPSECURITY_ATTRIBUTES, // [in, optional]
+    ) -> HANDLE;
+}
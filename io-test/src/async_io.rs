@@ -0,0 +1,204 @@
+use std::{
+    cell::RefCell,
+    ffi::CStr,
+    io::{self, IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write},
+    path::Path,
+    time::Duration,
+};
+
+use io_trait::{AsyncFile, AsyncIo, AsyncOperation, Io, OpenOptions, OperationResult};
+
+use crate::{invalid_input, MemFile, VirtualIo};
+
+fn to_path(path: &CStr) -> io::Result<&Path> {
+    path.to_str()
+        .map(Path::new)
+        .map_err(|_| invalid_input("the path is not valid UTF-8"))
+}
+
+/// Asynchronous file I/O over the file system of a [`VirtualIo`], created by
+/// [`VirtualIo::async_io`].
+///
+/// Every operation stays pending for [`VirtualAsyncIo::pending_polls`] calls of
+/// [`AsyncOperation::get_result`] and then completes. [`AsyncOperation::wait`] counts each
+/// pending poll as [`VirtualAsyncIo::POLL_TIME`] of its timeout. The data is transferred when the
+/// operation completes, so a dropped or canceled pending operation has no effect. Faults of
+/// [`VirtualIo::faults`] apply as for synchronous files.
+///
+/// Only polls make an operation progress, so [`AsyncOperation::set_waker`] wakes the waker at
+/// once and an [`io_trait::OperationFuture`] is polled again without waiting, once for each
+/// pending poll. An executor busy-polls such a future until it completes.
+pub struct VirtualAsyncIo<'a> {
+    io: &'a VirtualIo,
+    pending_polls: usize,
+}
+
+impl<'a> VirtualAsyncIo<'a> {
+    /// The virtual time of one pending poll.
+    pub const POLL_TIME: Duration = Duration::from_millis(1);
+    pub fn new(io: &'a VirtualIo) -> Self {
+        Self {
+            io,
+            pending_polls: 0,
+        }
+    }
+    /// The number of polls that return [`OperationResult::Pending`] before an operation
+    /// completes. The default is `0`.
+    pub fn pending_polls(mut self, polls: usize) -> Self {
+        self.pending_polls = polls;
+        self
+    }
+    fn file(&self, file: MemFile) -> VirtualAsyncFile {
+        VirtualAsyncFile {
+            file: RefCell::new(file),
+            pending_polls: self.pending_polls,
+        }
+    }
+}
+
+impl AsyncIo for VirtualAsyncIo<'_> {
+    type File = VirtualAsyncFile;
+    fn open_with(&self, path: &CStr, options: &OpenOptions) -> io::Result<Self::File> {
+        Ok(self.file(self.io.open_with(to_path(path)?, options)?))
+    }
+}
+
+/// A file opened by [`VirtualAsyncIo`].
+#[derive(Debug)]
+pub struct VirtualAsyncFile {
+    file: RefCell<MemFile>,
+    pending_polls: usize,
+}
+
+impl VirtualAsyncFile {
+    fn operation<'a>(&'a self, offset: u64, buffer: Buffer<'a>) -> VirtualOperation<'a> {
+        VirtualOperation {
+            pending_polls: self.pending_polls,
+            file: &self.file,
+            offset,
+            buffer,
+            result: None,
+        }
+    }
+}
+
+impl AsyncFile for VirtualAsyncFile {
+    type Operation<'a> = VirtualOperation<'a>;
+    fn read<'a>(&'a self, offset: u64, buffer: &'a mut [u8]) -> io::Result<Self::Operation<'a>> {
+        Ok(self.operation(offset, Buffer::Read(buffer)))
+    }
+    fn write<'a>(&'a self, offset: u64, buffer: &'a [u8]) -> io::Result<Self::Operation<'a>> {
+        Ok(self.operation(offset, Buffer::Write(buffer)))
+    }
+    fn read_vectored<'a>(
+        &'a self,
+        offset: u64,
+        buffers: &'a mut [IoSliceMut<'a>],
+    ) -> io::Result<Self::Operation<'a>> {
+        Ok(self.operation(offset, Buffer::ReadVectored(buffers)))
+    }
+    fn write_vectored<'a>(
+        &'a self,
+        offset: u64,
+        buffers: &'a [IoSlice<'a>],
+    ) -> io::Result<Self::Operation<'a>> {
+        Ok(self.operation(offset, Buffer::WriteVectored(buffers)))
+    }
+}
+
+#[derive(Debug)]
+enum Buffer<'a> {
+    Read(&'a mut [u8]),
+    Write(&'a [u8]),
+    ReadVectored(&'a mut [IoSliceMut<'a>]),
+    WriteVectored(&'a [IoSlice<'a>]),
+}
+
+/// Transfers consecutive parts of an operation. Like `preadv`, it stops at the first short or
+/// failed transfer and an error is only reported if nothing has been transferred.
+fn vectored(results: impl Iterator<Item = (io::Result<usize>, usize)>) -> io::Result<usize> {
+    let mut total = 0;
+    for (result, requested) in results {
+        match result {
+            Ok(len) => {
+                total += len;
+                if len < requested {
+                    break;
+                }
+            }
+            Err(e) if total == 0 => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(total)
+}
+
+/// An operation of [`VirtualAsyncFile`].
+#[derive(Debug)]
+pub struct VirtualOperation<'a> {
+    file: &'a RefCell<MemFile>,
+    offset: u64,
+    buffer: Buffer<'a>,
+    pending_polls: usize,
+    result: Option<io::Result<usize>>,
+}
+
+impl VirtualOperation<'_> {
+    fn complete(&mut self) -> io::Result<usize> {
+        let file = &mut *self.file.borrow_mut();
+        file.seek(SeekFrom::Start(self.offset))?;
+        match &mut self.buffer {
+            Buffer::Read(buffer) => file.read(buffer),
+            Buffer::Write(buffer) => file.write(buffer),
+            Buffer::ReadVectored(buffers) => vectored(buffers.iter_mut().map(|buffer| {
+                let len = buffer.len();
+                (file.read(buffer), len)
+            })),
+            Buffer::WriteVectored(buffers) => vectored(
+                buffers
+                    .iter()
+                    .map(|buffer| (file.write(buffer), buffer.len())),
+            ),
+        }
+    }
+}
+
+impl AsyncOperation for VirtualOperation<'_> {
+    fn get_result(&mut self) -> OperationResult {
+        if self.pending_polls > 0 {
+            self.pending_polls -= 1;
+            return OperationResult::Pending;
+        }
+        if self.result.is_none() {
+            self.result = Some(self.complete());
+        }
+        match self.result.as_ref().unwrap() {
+            Ok(len) => OperationResult::Ok(*len),
+            // `io::Error` isn't `Clone`, so the error is reported again with the same kind.
+            Err(e) => OperationResult::Err(io::Error::new(e.kind(), e.to_string())),
+        }
+    }
+    /// The remaining pending polls are skipped as far as `timeout` covers them, see
+    /// [`VirtualAsyncIo::POLL_TIME`].
+    fn wait(&mut self, timeout: Option<Duration>) -> OperationResult {
+        let polls = timeout.map_or(usize::MAX, |timeout| {
+            let polls = timeout.as_nanos() / VirtualAsyncIo::POLL_TIME.as_nanos();
+            polls.try_into().unwrap_or(usize::MAX)
+        });
+        if polls < self.pending_polls {
+            self.pending_polls -= polls;
+            return OperationResult::Pending;
+        }
+        self.pending_polls = 0;
+        self.get_result()
+    }
+    /// A pending operation is canceled without transferring data.
+    fn cancel(&mut self) -> bool {
+        if self.result.is_some() {
+            return false;
+        }
+        self.pending_polls = 0;
+        self.result = Some(Err(io::Error::other("the operation is canceled")));
+        true
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem::take,
+    rc::Rc,
+};
+
+use crate::{rng::Rng, split_parent, Entity, FileSystem, NodeRef};
+
+/// Unsynced file data is torn in blocks of this size, like disk sectors.
+const BLOCK_SIZE: usize = 512;
+
+/// How [`VirtualIo::crash`](crate::VirtualIo::crash) treats state that isn't durable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crash {
+    /// All unsynced file data and directory entries are lost.
+    Discard,
+    /// Each unsynced directory entry and each block of unsynced file data randomly
+    /// survives or is lost. The same seed gives the same result.
+    Tear { seed: u64 },
+}
+
+type Children = BTreeMap<Vec<u8>, Entity>;
+
+fn tear_data(rng: &mut Rng, old: &[u8], new: &[u8]) -> Vec<u8> {
+    let len = if rng.bool() { old.len() } else { new.len() };
+    let mut result = vec![0; len];
+    for (i, block) in result.chunks_mut(BLOCK_SIZE).enumerate() {
+        let source = if rng.bool() { new } else { old };
+        let start = i * BLOCK_SIZE;
+        if let Some(source) = source.get(start..) {
+            let len = source.len().min(block.len());
+            block[..len].copy_from_slice(&source[..len]);
+        }
+    }
+    result
+}
+
+fn crash_node(node: &NodeRef, rng: Option<&mut Rng>) {
+    let mut node = node.0.borrow_mut();
+    let data = match rng {
+        Some(rng) => tear_data(rng, &node.synced, &node.data),
+        None => node.synced.clone(),
+    };
+    node.synced = data.clone();
+    node.data = data;
+}
+
+impl FileSystem {
+    pub(crate) fn new_dir_id(&mut self) -> u64 {
+        self.next_dir_id += 1;
+        self.next_dir_id
+    }
+    fn dir_id(&self, path: &[u8]) -> Option<u64> {
+        match self.entity_map.get(path) {
+            _ if path.is_empty() => Some(0),
+            Some(Entity::Dir(id, _)) => Some(*id),
+            _ => None,
+        }
+    }
+    /// Returns entries of all directories by their ids.
+    fn snapshot(&self) -> BTreeMap<u64, Children> {
+        let mut result: BTreeMap<u64, Children> = BTreeMap::default();
+        for (path, entity) in &self.entity_map {
+            let (parent, name) = split_parent(path).unwrap_or((b"", path));
+            if let Some(id) = self.dir_id(parent) {
+                result
+                    .entry(id)
+                    .or_default()
+                    .insert(name.to_vec(), entity.clone());
+            }
+        }
+        result
+    }
+    /// Makes the current entries of the directory durable.
+    pub(crate) fn sync_dir(&mut self, dir: &[u8]) {
+        let Some(id) = self.dir_id(dir) else {
+            return;
+        };
+        let prefix = if dir.is_empty() {
+            Vec::default()
+        } else {
+            [dir, b"/"].concat()
+        };
+        let children = self
+            .entity_map
+            .range(prefix.clone()..)
+            .take_while(|(p, _)| p.starts_with(&prefix))
+            .filter(|(p, _)| !p[prefix.len()..].contains(&b'/'))
+            .map(|(p, e)| (p[prefix.len()..].to_vec(), e.clone()))
+            .collect();
+        self.durable.insert(id, children);
+    }
+    /// Randomly picks either the durable or the current version of each entry.
+    fn tear_tree(&mut self, rng: &mut Rng) -> BTreeMap<u64, Children> {
+        let mut current = self.snapshot();
+        let mut durable = take(&mut self.durable);
+        let ids: BTreeSet<u64> = current.keys().chain(durable.keys()).copied().collect();
+        for id in ids {
+            let c = current.entry(id).or_default();
+            let mut d = durable.remove(&id).unwrap_or_default();
+            let names: BTreeSet<Vec<u8>> = c.keys().chain(d.keys()).cloned().collect();
+            for name in names {
+                let e = d.remove(&name);
+                if rng.bool() {
+                    match e {
+                        Some(e) => c.insert(name, e),
+                        None => c.remove(&name),
+                    };
+                }
+            }
+        }
+        current
+    }
+    /// Rebuilds entries of the directory `id` at `path` from `tree`. A directory that is
+    /// reachable from more than one parent is kept only at the first one.
+    fn build(
+        &mut self,
+        tree: &mut BTreeMap<u64, Children>,
+        visited: &mut BTreeSet<u64>,
+        id: u64,
+        path: &[u8],
+    ) {
+        for (name, entity) in tree.remove(&id).unwrap_or_default() {
+            let p = if path.is_empty() {
+                name
+            } else {
+                [path, b"/", &name].concat()
+            };
+            match entity {
+                Entity::Dir(child, _) => {
+                    if visited.insert(child) {
+                        self.entity_map.insert(p.clone(), entity);
+                        self.build(tree, visited, child, &p);
+                    }
+                }
+                _ => {
+                    self.entity_map.insert(p, entity);
+                }
+            }
+        }
+    }
+    pub(crate) fn crash(&mut self, crash: Crash) {
+        let mut rng = match crash {
+            Crash::Discard => None,
+            Crash::Tear { seed } => Some(Rng::new(seed)),
+        };
+        let mut tree = match &mut rng {
+            Some(rng) => self.tear_tree(rng),
+            None => take(&mut self.durable),
+        };
+        self.entity_map.clear();
+        self.build(&mut tree, &mut BTreeSet::from([0]), 0, b"");
+        let mut nodes = BTreeSet::default();
+        for entity in self.entity_map.values() {
+            if let Entity::File(node) = entity {
+                if nodes.insert(Rc::as_ptr(&node.0)) {
+                    crash_node(node, rng.as_mut());
+                }
+            }
+        }
+        // everything that survived is durable.
+        self.durable = self.snapshot();
+    }
+}
@@ -0,0 +1,142 @@
+use std::{io, path::Path};
+
+use io_trait::Pattern;
+
+use crate::rng::Rng;
+
+/// A call of [`VirtualIo`](crate::VirtualIo) or [`MemFile`](crate::MemFile) that can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Call {
+    /// Opening a file with `create` or `create_new`.
+    Create,
+    /// Opening an existing file.
+    Open,
+    /// `Write::write` on a file.
+    Write,
+}
+
+/// Fails matching calls with an error of the given kind.
+#[derive(Debug, Clone)]
+pub struct Fault {
+    call: Call,
+    kind: io::ErrorKind,
+    nth: Option<usize>,
+    pattern: Option<Pattern>,
+    count: usize,
+}
+
+impl Fault {
+    /// Fails every `call`, unless restricted by [`Fault::nth`] or [`Fault::path`].
+    pub fn new(call: Call, kind: io::ErrorKind) -> Self {
+        Self {
+            call,
+            kind,
+            nth: None,
+            pattern: None,
+            count: 0,
+        }
+    }
+    /// Fails only the `nth` matching call, starting from `1`.
+    pub fn nth(mut self, nth: usize) -> Self {
+        self.nth = Some(nth);
+        self
+    }
+    /// Matches only calls on files whose normalized path matches `pattern`.
+    pub fn path(mut self, pattern: Pattern) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+    fn check(&mut self, call: Call, path: &Path) -> bool {
+        if self.call != call || self.pattern.as_ref().is_some_and(|p| !p.matches(path)) {
+            return false;
+        }
+        self.count += 1;
+        self.nth.is_none_or(|n| n == self.count)
+    }
+}
+
+/// Makes file reads and writes transfer fewer bytes than requested, like pipes and
+/// network file systems do.
+#[derive(Debug, Clone)]
+pub struct ShortIo {
+    chunk: Option<usize>,
+    interrupt: u64,
+    rng: Rng,
+}
+
+impl ShortIo {
+    /// Transfers a random number of bytes, from `1` to the requested length.
+    pub fn random(seed: u64) -> Self {
+        Self {
+            chunk: None,
+            interrupt: 0,
+            rng: Rng::new(seed),
+        }
+    }
+    /// Transfers at most `len` bytes.
+    pub fn fixed(len: usize) -> Self {
+        assert!(len > 0, "the chunk length must be positive");
+        Self {
+            chunk: Some(len),
+            interrupt: 0,
+            rng: Rng::new(0),
+        }
+    }
+    /// Fails about one in `n` calls with [`io::ErrorKind::Interrupted`].
+    pub fn interrupt(mut self, n: u64) -> Self {
+        self.interrupt = n;
+        self
+    }
+    /// Returns how many of `len` requested bytes to transfer.
+    fn len(&mut self, len: usize) -> io::Result<usize> {
+        if len == 0 {
+            return Ok(0);
+        }
+        if self.interrupt > 0 && self.rng.below(self.interrupt) == 0 {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+        }
+        Ok(match self.chunk {
+            Some(chunk) => chunk.min(len),
+            None => 1 + self.rng.below(len as u64) as usize,
+        })
+    }
+}
+
+/// A set of faults shared by a [`VirtualIo`](crate::VirtualIo) and its files.
+#[derive(Debug, Default, Clone)]
+pub struct FaultPlan {
+    faults: Vec<Fault>,
+    short_io: Option<ShortIo>,
+}
+
+impl FaultPlan {
+    pub fn add(&mut self, fault: Fault) -> &mut Self {
+        self.faults.push(fault);
+        self
+    }
+    pub fn short_io(&mut self, short_io: ShortIo) -> &mut Self {
+        self.short_io = Some(short_io);
+        self
+    }
+    pub fn clear(&mut self) {
+        self.faults.clear();
+        self.short_io = None;
+    }
+    /// Returns how many of `len` bytes a file read or write should transfer.
+    pub(crate) fn transfer_len(&mut self, len: usize) -> io::Result<usize> {
+        match &mut self.short_io {
+            Some(short_io) => short_io.len(len),
+            None => Ok(len),
+        }
+    }
+    /// Returns an error of the first fault that fires. Every matching fault counts the call.
+    pub(crate) fn check(&mut self, call: Call, path: &Path) -> io::Result<()> {
+        let mut result = Ok(());
+        for fault in &mut self.faults {
+            if fault.check(call, path) && result.is_ok() {
+                result = Err(io::Error::new(fault.kind, "injected fault"));
+            }
+        }
+        result
+    }
+}
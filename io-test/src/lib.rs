@@ -1,29 +1,74 @@
+mod async_io;
+mod crash;
+mod fault;
+mod rng;
+
+pub use async_io::{VirtualAsyncFile, VirtualAsyncIo, VirtualOperation};
+pub use crash::Crash;
+pub use fault::{Call, Fault, FaultPlan, ShortIo};
+
 use std::{
     cell::RefCell,
-    collections::BTreeMap,
+    collections::{btree_map, BTreeMap},
+    env::VarError,
+    ffi::OsStr,
     io::{self, Read, Seek, Write},
     iter::once,
-    ops::Add,
+    ops::{Add, Bound},
+    path::{Path, PathBuf},
     rc::Rc,
     str::from_utf8,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
     vec,
 };
 
-use io_trait::{File, Io};
+use io_trait::{File, FileType, Io, OpenOptions};
+
+/// Times are measured by the virtual clock of [`VirtualIo`].
+#[derive(Debug, Clone, Copy)]
+struct Attributes {
+    created: Duration,
+    modified: Duration,
+    accessed: Duration,
+    read_only: bool,
+}
+
+impl Attributes {
+    fn new(now: Duration) -> Self {
+        Self {
+            created: now,
+            modified: now,
+            accessed: now,
+            read_only: false,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Metadata {
     len: u64,
-    is_dir: bool,
+    file_type: FileType,
+    attributes: Attributes,
 }
 
 impl io_trait::Metadata for Metadata {
     fn len(&self) -> u64 {
         self.len
     }
-    fn is_dir(&self) -> bool {
-        self.is_dir
+    fn file_type(&self) -> FileType {
+        self.file_type
+    }
+    fn is_read_only(&self) -> bool {
+        self.attributes.read_only
+    }
+    fn modified(&self) -> io::Result<SystemTime> {
+        Ok(UNIX_EPOCH + self.attributes.modified)
+    }
+    fn accessed(&self) -> io::Result<SystemTime> {
+        Ok(UNIX_EPOCH + self.attributes.accessed)
+    }
+    fn created(&self) -> io::Result<SystemTime> {
+        Ok(UNIX_EPOCH + self.attributes.created)
     }
 }
 
@@ -48,14 +93,23 @@ impl VecRef {
         }
         from_utf8(&result).unwrap().to_string()
     }
-    fn len(&self) -> u64 {
-        self.0.borrow().len() as u64
+}
+
+/// Reading consumes the data, so a `VecRef` can be used as a pipe, for example, to script
+/// stdin.
+impl Read for VecRef {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut v = self.0.borrow_mut();
+        let len = v.len().min(buf.len());
+        buf[..len].copy_from_slice(&v[..len]);
+        v.drain(..len);
+        Ok(len)
     }
-    fn metadata(&self) -> Metadata {
-        Metadata {
-            len: self.len(),
-            is_dir: false,
-        }
+}
+
+impl From<&[u8]> for VecRef {
+    fn from(data: &[u8]) -> Self {
+        Self(Rc::new(RefCell::new(data.to_vec())))
     }
 }
 
@@ -69,55 +123,193 @@ impl Write for VecRef {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
+struct Node {
+    data: Vec<u8>,
+    attributes: Attributes,
+    /// The data at the last sync point.
+    synced: Vec<u8>,
+    sync_count: usize,
+}
+
+/// File content and attributes shared by all handles of the file.
+#[derive(Debug, Clone)]
+struct NodeRef(Rc<RefCell<Node>>);
+
+impl NodeRef {
+    fn new(now: Duration) -> Self {
+        Self(Rc::new(RefCell::new(Node {
+            data: Vec::default(),
+            attributes: Attributes::new(now),
+            synced: Vec::default(),
+            sync_count: 0,
+        })))
+    }
+    fn len(&self) -> u64 {
+        self.0.borrow().data.len() as u64
+    }
+    fn sync(&self) {
+        let mut node = self.0.borrow_mut();
+        node.synced = node.data.clone();
+        node.sync_count += 1;
+    }
+    fn metadata(&self) -> Metadata {
+        let node = self.0.borrow();
+        Metadata {
+            len: node.data.len() as u64,
+            file_type: FileType::File,
+            attributes: node.attributes,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 enum Entity {
-    #[default]
-    Dir,
-    File(VecRef),
+    /// Directories have a unique id, so their durable entries follow them when they are
+    /// renamed. The root directory has id `0`.
+    Dir(u64, Attributes),
+    /// Hard links share the same node.
+    File(NodeRef),
+    /// A symbolic link to a path relative to the link's directory, or to the root if the
+    /// path starts with `/`.
+    Symlink(Vec<u8>, Attributes),
 }
 
 impl Entity {
     fn metadata(&self) -> Metadata {
         match self {
-            Entity::Dir => Metadata {
+            Entity::Dir(_, attributes) => Metadata {
                 len: 0,
-                is_dir: true,
+                file_type: FileType::Dir,
+                attributes: *attributes,
             },
             Entity::File(x) => x.metadata(),
+            Entity::Symlink(target, attributes) => Metadata {
+                len: target.len() as u64,
+                file_type: FileType::Symlink,
+                attributes: *attributes,
+            },
         }
     }
 }
 
+const MAX_SYMLINKS: usize = 40;
+
+/// Names are stored as `/` separated byte strings, see [`OsStr::as_encoded_bytes`], so any
+/// path round-trips, even if it isn't valid UTF-8.
 #[derive(Debug, Default)]
 pub struct FileSystem {
-    entity_map: BTreeMap<String, Entity>,
+    entity_map: BTreeMap<Vec<u8>, Entity>,
+    next_dir_id: u64,
+    /// Entries of each directory, by id, at the last [`Io::sync_dir`].
+    durable: BTreeMap<u64, BTreeMap<Vec<u8>, Entity>>,
+}
+
+fn to_bytes(path: &Path) -> &[u8] {
+    path.as_os_str().as_encoded_bytes()
+}
+
+fn to_path(bytes: &[u8]) -> PathBuf {
+    // SAFETY: all names are built from `OsStr::as_encoded_bytes` split and joined at `/`.
+    unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }.into()
+}
+
+/// Joins `name` to `dir` with `/`, which [`FileSystem`] expects on all platforms, unlike
+/// [`Path::join`].
+fn join(dir: &Path, name: &[u8]) -> PathBuf {
+    let mut path = to_bytes(dir).to_vec();
+    if !path.is_empty() && !path.ends_with(b"/") {
+        path.push(b'/');
+    }
+    path.extend_from_slice(name);
+    to_path(&path)
+}
+
+fn split_parent(path: &[u8]) -> Option<(&[u8], &[u8])> {
+    path.iter()
+        .rposition(|&c| c == b'/')
+        .map(|i| (&path[..i], &path[i + 1..]))
 }
 
 impl FileSystem {
-    pub fn check_dir(&self, path: &str) -> io::Result<()> {
-        if let Some(Entity::Dir) = self.entity_map.get(path) {
+    /// The root directory is represented by an empty path.
+    pub fn check_dir(&self, path: &[u8]) -> io::Result<()> {
+        if path.is_empty() {
+            return Ok(());
+        }
+        if let Some(Entity::Dir(..)) = self.entity_map.get(path) {
             Ok(())
         } else {
             Err(not_found())
         }
     }
-    pub fn check_parent(&self, path: &str) -> io::Result<()> {
-        if let Some(d) = path.rfind('/').map(|i| &path[..i]) {
+    pub fn check_parent(&self, path: &[u8]) -> io::Result<()> {
+        if let Some((d, _)) = split_parent(path) {
             self.check_dir(d)
         } else {
             Ok(())
         }
     }
+    fn metadata(&self, path: &[u8]) -> io::Result<Metadata> {
+        if path.is_empty() {
+            // the root directory has no entry, see `FileSystem::check_dir`.
+            return Ok(Entity::Dir(0, Attributes::new(Duration::ZERO)).metadata());
+        }
+        self.entity_map
+            .get(path)
+            .map(Entity::metadata)
+            .ok_or_else(not_found)
+    }
+    /// Normalizes `path` and replaces symbolic links in it with their targets. The last
+    /// component is replaced only if `follow` is `true`.
+    fn resolve(&self, path: &[u8], follow: bool) -> io::Result<Vec<u8>> {
+        let mut pending: Vec<&[u8]> = path.rsplit(|&c| c == b'/').collect();
+        let mut result: Vec<&[u8]> = Vec::default();
+        let mut hops = 0;
+        while let Some(c) = pending.pop() {
+            match c {
+                b"" | b"." => continue,
+                b".." => {
+                    result.pop();
+                    continue;
+                }
+                _ => {}
+            }
+            result.push(c);
+            if pending.is_empty() && !follow {
+                break;
+            }
+            if let Some(Entity::Symlink(target, _)) = self.entity_map.get(&result.join(&b'/')) {
+                hops += 1;
+                if hops > MAX_SYMLINKS {
+                    return Err(io::Error::other("too many levels of symbolic links"));
+                }
+                result.pop();
+                if target.starts_with(b"/") {
+                    result.clear();
+                }
+                pending.extend(target.rsplit(|&c| c == b'/'));
+            }
+        }
+        Ok(result.join(&b'/'))
+    }
+    fn has_children(&self, path: &[u8]) -> bool {
+        let prefix = [path, b"/"].concat();
+        self.entity_map
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|(p, _)| p.starts_with(&prefix))
+    }
 }
 
 pub struct DirEntry {
-    path: String,
+    path: PathBuf,
     metadata: Metadata,
 }
 
 impl io_trait::DirEntry for DirEntry {
     type Metadata = Metadata;
-    fn path(&self) -> String {
+    fn path(&self) -> PathBuf {
         self.path.clone()
     }
     fn metadata(&self) -> io::Result<Self::Metadata> {
@@ -125,42 +317,153 @@ impl io_trait::DirEntry for DirEntry {
     }
 }
 
+/// Iterates over a directory without holding a borrow of the [`FileSystem`], so
+/// entries added or removed during iteration are seen or skipped accordingly.
+pub struct ReadDir<'a> {
+    fs: &'a RefCell<FileSystem>,
+    path: PathBuf,
+    prefix: Vec<u8>,
+    cursor: Bound<Vec<u8>>,
+}
+
+impl Iterator for ReadDir<'_> {
+    type Item = io::Result<DirEntry>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let fs = self.fs.borrow();
+        loop {
+            let (key, entity) = fs
+                .entity_map
+                .range((self.cursor.clone(), Bound::Unbounded))
+                .next()?;
+            let name = key.strip_prefix(self.prefix.as_slice())?;
+            if let Some(i) = name.iter().position(|&c| c == b'/') {
+                // skip the subtree, `0` is the byte that follows `/`.
+                self.cursor = Bound::Included([&key[..self.prefix.len() + i], b"0"].concat());
+                continue;
+            }
+            self.cursor = Bound::Excluded(key.clone());
+            return Some(Ok(DirEntry {
+                path: join(&self.path, name),
+                metadata: entity.metadata(),
+            }));
+        }
+    }
+}
+
 pub struct VirtualIo {
     pub args: Vec<String>,
+    pub env: RefCell<BTreeMap<String, String>>,
     pub fs: RefCell<FileSystem>,
+    pub stdin: VecRef,
     pub stdout: VecRef,
-    pub duration: RefCell<Duration>,
+    pub stderr: VecRef,
+    pub duration: Rc<RefCell<Duration>>,
+    pub faults: Rc<RefCell<FaultPlan>>,
 }
 
 impl VirtualIo {
     pub fn new(args: &[&str]) -> Self {
+        Self::with_env(args, &[])
+    }
+    pub fn with_env(args: &[&str], env: &[(&str, &str)]) -> Self {
         Self {
             args: once("blockset".to_string())
                 .chain(args.iter().map(|v| v.to_string()))
                 .collect(),
+            env: RefCell::new(
+                env.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
             fs: Default::default(),
+            stdin: Default::default(),
             stdout: Default::default(),
+            stderr: Default::default(),
             duration: Default::default(),
+            faults: Default::default(),
         }
     }
+    fn clock(&self) -> Duration {
+        *self.duration.borrow()
+    }
+    fn node(&self, path: &Path) -> io::Result<NodeRef> {
+        let fs = self.fs.borrow();
+        match fs.entity_map.get(&fs.resolve(to_bytes(path), true)?) {
+            Some(Entity::File(x)) => Ok(x.clone()),
+            Some(_) => Err(is_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+    /// Returns the file data at its last sync point.
+    pub fn synced_data(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        Ok(self.node(path.as_ref())?.0.borrow().synced.clone())
+    }
+    /// Simulates a power loss. Directory entries survive only if their directory was
+    /// synced, and file data only if the file was synced, see [`Crash`]. Handles of files
+    /// opened before the crash should not be used anymore.
+    pub fn crash(&self, crash: Crash) {
+        self.fs.borrow_mut().crash(crash);
+    }
+    /// Returns asynchronous file I/O over the same file system.
+    pub fn async_io(&self) -> VirtualAsyncIo<'_> {
+        VirtualAsyncIo::new(self)
+    }
+    /// Returns how many times the file has been synced.
+    pub fn sync_count(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        Ok(self.node(path.as_ref())?.0.borrow().sync_count)
+    }
 }
 
 #[derive(Debug)]
 pub struct MemFile {
-    vec_ref: VecRef,
+    node: NodeRef,
+    duration: Rc<RefCell<Duration>>,
+    faults: Rc<RefCell<FaultPlan>>,
+    /// The normalized path the file was opened with.
+    path: PathBuf,
     pos: usize,
+    read: bool,
+    write: bool,
+    append: bool,
 }
 
 impl MemFile {
-    fn new(vec_ref: VecRef) -> Self {
-        Self { vec_ref, pos: 0 }
+    fn new(node: NodeRef, io: &VirtualIo, path: PathBuf, options: &OpenOptions) -> Self {
+        Self {
+            node,
+            duration: io.duration.clone(),
+            faults: io.faults.clone(),
+            path,
+            pos: 0,
+            read: options.read,
+            write: options.is_writable(),
+            append: options.append,
+        }
     }
 }
 
 impl File for MemFile {
     type Metadata = Metadata;
     fn metadata(&self) -> io::Result<Self::Metadata> {
-        Ok(self.vec_ref.metadata())
+        Ok(self.node.metadata())
+    }
+    fn sync_all(&self) -> io::Result<()> {
+        self.node.sync();
+        Ok(())
+    }
+    /// Attributes are not durable state in memory, so it's the same as `sync_all`.
+    fn sync_data(&self) -> io::Result<()> {
+        self.node.sync();
+        Ok(())
+    }
+    fn set_len(&self, size: u64) -> io::Result<()> {
+        if !self.write {
+            return Err(permission_denied("the file is not opened for writing"));
+        }
+        let mut node = self.node.0.borrow_mut();
+        node.data.resize(size as usize, 0);
+        node.attributes.modified = *self.duration.borrow();
+        Ok(())
     }
 }
 
@@ -168,7 +471,7 @@ impl Seek for MemFile {
     fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
         self.pos = match pos {
             io::SeekFrom::Start(x) => x as usize,
-            io::SeekFrom::End(x) => (self.vec_ref.len() as i64 + x) as usize,
+            io::SeekFrom::End(x) => (self.node.len() as i64 + x) as usize,
             io::SeekFrom::Current(x) => (self.pos as i64 + x) as usize,
         };
         Ok(self.pos as u64)
@@ -177,8 +480,14 @@ impl Seek for MemFile {
 
 impl Read for MemFile {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let source = &self.vec_ref.0.borrow()[self.pos..];
-        let len = source.len().min(buf.len());
+        if !self.read {
+            return Err(permission_denied("the file is not opened for reading"));
+        }
+        let max_len = self.faults.borrow_mut().transfer_len(buf.len())?;
+        let mut node = self.node.0.borrow_mut();
+        node.attributes.accessed = *self.duration.borrow();
+        let source = &node.data[self.pos.min(node.data.len())..];
+        let len = source.len().min(max_len);
         buf[..len].copy_from_slice(&source[..len]);
         self.pos += len;
         Ok(len)
@@ -187,11 +496,25 @@ impl Read for MemFile {
 
 impl Write for MemFile {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.write {
+            return Err(permission_denied("the file is not opened for writing"));
+        }
+        let len = {
+            let mut faults = self.faults.borrow_mut();
+            faults.check(Call::Write, &self.path)?;
+            faults.transfer_len(buf.len())?
+        };
+        let buf = &buf[..len];
+        if self.append {
+            self.pos = self.node.len() as usize;
+        }
         let pos = self.pos;
         let buf_len = buf.len();
         let end = pos + buf_len;
         {
-            let mut v = self.vec_ref.0.borrow_mut();
+            let mut node = self.node.0.borrow_mut();
+            node.attributes.modified = *self.duration.borrow();
+            let v = &mut node.data;
             if end > v.len() {
                 v.resize(end, 0);
             }
@@ -201,7 +524,7 @@ impl Write for MemFile {
         Ok(buf_len)
     }
     fn flush(&mut self) -> io::Result<()> {
-        self.vec_ref.flush()
+        Ok(())
     }
 }
 
@@ -209,10 +532,33 @@ fn not_found() -> io::Error {
     io::Error::new(io::ErrorKind::NotFound, "file not found")
 }
 
-fn check_path(a: &str) -> io::Result<()> {
-    if a.chars()
-        .all(|c| c.is_ascii_alphanumeric() || "/_.-".contains(c))
-    {
+fn is_a_directory() -> io::Error {
+    io::Error::new(io::ErrorKind::IsADirectory, "is a directory")
+}
+
+fn not_a_directory() -> io::Error {
+    io::Error::new(io::ErrorKind::NotADirectory, "not a directory")
+}
+
+fn directory_not_empty() -> io::Error {
+    io::Error::new(io::ErrorKind::DirectoryNotEmpty, "directory not empty")
+}
+
+fn already_exists() -> io::Error {
+    io::Error::new(io::ErrorKind::AlreadyExists, "file already exists")
+}
+
+fn permission_denied(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, message.to_string())
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.to_string())
+}
+
+/// Rejects names which are not portable, for example, names that are not allowed on Windows.
+fn check_path(a: &[u8]) -> io::Result<()> {
+    if a.iter().all(|c| !b"\0\\:*?\"<>|".contains(c)) {
         Ok(())
     } else {
         Err(io::Error::new(
@@ -224,82 +570,134 @@ fn check_path(a: &str) -> io::Result<()> {
 
 impl Io for VirtualIo {
     type File = MemFile;
+    type Stdin = VecRef;
     type Stdout = VecRef;
+    type Stderr = VecRef;
     type Args = vec::IntoIter<String>;
+    type Vars = btree_map::IntoIter<String, String>;
     type Metadata = Metadata;
     type DirEntry = DirEntry;
+    type ReadDir<'a> = ReadDir<'a>;
     type Instant = Duration;
     fn args(&self) -> Self::Args {
         self.args.clone().into_iter()
     }
-    fn metadata(&self, path: &str) -> io::Result<Metadata> {
+    fn var(&self, key: &str) -> Result<String, VarError> {
+        self.env
+            .borrow()
+            .get(key)
+            .cloned()
+            .ok_or(VarError::NotPresent)
+    }
+    fn vars(&self) -> Self::Vars {
+        self.env.borrow().clone().into_iter()
+    }
+    fn set_var(&self, key: &str, value: &str) {
+        self.env
+            .borrow_mut()
+            .insert(key.to_string(), value.to_string());
+    }
+    fn remove_var(&self, key: &str) {
+        self.env.borrow_mut().remove(key);
+    }
+    fn metadata(&self, path: impl AsRef<Path>) -> io::Result<Metadata> {
+        let path = to_bytes(path.as_ref());
         let fs = self.fs.borrow();
-        let dir_end = path.ends_with('/');
+        let dir_end = path.ends_with(b"/");
         let path = if dir_end {
             &path[..path.len() - 1]
         } else {
             path
         };
-        let result = fs
-            .entity_map
-            .get(path)
-            .map(Entity::metadata)
-            .ok_or_else(not_found)?;
-        if !result.is_dir && dir_end {
+        let result = fs.metadata(&fs.resolve(path, true)?)?;
+        if !result.file_type.is_dir() && dir_end {
             return Err(not_found());
         }
         Ok(result)
     }
-    fn create(&self, path: &str) -> io::Result<Self::File> {
+    fn symlink_metadata(&self, path: impl AsRef<Path>) -> io::Result<Metadata> {
+        let fs = self.fs.borrow();
+        fs.metadata(&fs.resolve(to_bytes(path.as_ref()), false)?)
+    }
+    fn open_with(&self, path: impl AsRef<Path>, options: &OpenOptions) -> io::Result<Self::File> {
+        options.check()?;
         let mut fs = self.fs.borrow_mut();
-        fs.check_parent(path)?;
-        let vec_ref = VecRef::default();
-        check_path(path)?;
-        fs.entity_map
-            .insert(path.to_string(), Entity::File(vec_ref.clone()));
-        Ok(MemFile::new(vec_ref))
+        let path = fs.resolve(to_bytes(path.as_ref()), !options.create_new)?;
+        fs.check_parent(&path)?;
+        check_path(&path)?;
+        let call = if options.create || options.create_new {
+            Call::Create
+        } else {
+            Call::Open
+        };
+        self.faults.borrow_mut().check(call, &to_path(&path))?;
+        let node = match fs.entity_map.get(&path) {
+            Some(Entity::File(x)) => {
+                if options.create_new {
+                    return Err(already_exists());
+                }
+                let mut node = x.0.borrow_mut();
+                if options.is_writable() && node.attributes.read_only {
+                    return Err(permission_denied("the file is read-only"));
+                }
+                if options.truncate {
+                    node.data.clear();
+                    node.attributes.modified = self.clock();
+                }
+                x.clone()
+            }
+            Some(Entity::Dir(..)) => return Err(is_a_directory()),
+            Some(Entity::Symlink(..)) => return Err(already_exists()),
+            None => {
+                if !options.create && !options.create_new {
+                    return Err(not_found());
+                }
+                let node = NodeRef::new(self.clock());
+                fs.entity_map
+                    .insert(path.clone(), Entity::File(node.clone()));
+                node
+            }
+        };
+        Ok(MemFile::new(node, self, to_path(&path), options))
     }
-    fn create_dir(&self, path: &str) -> io::Result<()> {
+    fn create_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
         let mut fs = self.fs.borrow_mut();
-        fs.entity_map.insert(path.to_string(), Entity::Dir);
+        let path = fs.resolve(to_bytes(path.as_ref()), false)?;
+        // the root directory always exists, see `FileSystem::check_dir`.
+        if path.is_empty() || fs.entity_map.contains_key(&path) {
+            return Err(already_exists());
+        }
+        fs.check_parent(&path)?;
+        let id = fs.new_dir_id();
+        fs.entity_map
+            .insert(path, Entity::Dir(id, Attributes::new(self.clock())));
         Ok(())
     }
-    fn open(&self, path: &str) -> io::Result<Self::File> {
-        let fs = self.fs.borrow();
-        fs.check_parent(path)?;
-        check_path(path)?;
-        fs.entity_map
-            .get(path)
-            .and_then(|v| {
-                if let Entity::File(x) = v {
-                    Some(MemFile::new(x.to_owned()))
-                } else {
-                    None
-                }
-            })
-            .ok_or_else(not_found)
+    fn stdin(&self) -> VecRef {
+        self.stdin.clone()
     }
     fn stdout(&self) -> VecRef {
         self.stdout.clone()
     }
+    fn stderr(&self) -> VecRef {
+        self.stderr.clone()
+    }
 
-    fn read_dir(&self, path: &str) -> io::Result<Vec<DirEntry>> {
+    fn read_dir(&self, path: impl AsRef<Path>) -> io::Result<ReadDir<'_>> {
         let fs = self.fs.borrow();
-        fs.check_dir(path)?;
-        let i = fs.entity_map.iter().map(|(p, e)| DirEntry {
-            path: p.to_owned(),
-            metadata: e.metadata(),
-        });
-        let x = i
-            .filter(|p| {
-                if let Some((a, _)) = p.path.rsplit_once('/') {
-                    a == path
-                } else {
-                    false
-                }
-            })
-            .collect();
-        Ok(x)
+        let dir = fs.resolve(to_bytes(path.as_ref()), true)?;
+        fs.check_dir(&dir)?;
+        let prefix = if dir.is_empty() {
+            dir
+        } else {
+            [dir.as_slice(), b"/"].concat()
+        };
+        Ok(ReadDir {
+            fs: &self.fs,
+            path: path.as_ref().to_path_buf(),
+            cursor: Bound::Included(prefix.clone()),
+            prefix,
+        })
     }
 
     fn now(&self) -> Self::Instant {
@@ -309,11 +707,11 @@ impl Io for VirtualIo {
         result
     }
 
-    fn current_dir(&self) -> io::Result<String> {
-        Ok(String::default())
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(PathBuf::default())
     }
-    fn set_current_dir(&self, path: &str) -> io::Result<()> {
-        if path.is_empty() {
+    fn set_current_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        if path.as_ref().as_os_str().is_empty() {
             Ok(())
         } else {
             Err(io::Error::new(
@@ -322,16 +720,187 @@ impl Io for VirtualIo {
             ))
         }
     }
+    fn remove_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut fs = self.fs.borrow_mut();
+        let path = fs.resolve(to_bytes(path.as_ref()), false)?;
+        fs.check_parent(&path)?;
+        match fs.entity_map.get(&path) {
+            Some(Entity::File(_) | Entity::Symlink(..)) => {
+                fs.entity_map.remove(&path);
+                Ok(())
+            }
+            Some(Entity::Dir(..)) => Err(is_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+    fn remove_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut fs = self.fs.borrow_mut();
+        let path = fs.resolve(to_bytes(path.as_ref()), false)?;
+        match fs.entity_map.get(&path) {
+            Some(Entity::Dir(..)) => {
+                if fs.has_children(&path) {
+                    return Err(directory_not_empty());
+                }
+                fs.entity_map.remove(&path);
+                Ok(())
+            }
+            Some(Entity::File(_) | Entity::Symlink(..)) => Err(not_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+    fn remove_dir_all(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut fs = self.fs.borrow_mut();
+        let path = fs.resolve(to_bytes(path.as_ref()), false)?;
+        match fs.entity_map.get(&path) {
+            Some(Entity::Dir(..)) => {
+                let prefix = [&path, b"/".as_slice()].concat();
+                fs.entity_map
+                    .retain(|p, _| *p != path && !p.starts_with(&prefix));
+                Ok(())
+            }
+            Some(Entity::Symlink(..)) => {
+                fs.entity_map.remove(&path);
+                Ok(())
+            }
+            Some(Entity::File(_)) => Err(not_a_directory()),
+            None => Err(not_found()),
+        }
+    }
+    fn set_read_only(&self, path: impl AsRef<Path>, read_only: bool) -> io::Result<()> {
+        let mut fs = self.fs.borrow_mut();
+        let path = fs.resolve(to_bytes(path.as_ref()), true)?;
+        match fs.entity_map.get_mut(&path) {
+            Some(Entity::Dir(_, attributes) | Entity::Symlink(_, attributes)) => {
+                attributes.read_only = read_only
+            }
+            Some(Entity::File(x)) => x.0.borrow_mut().attributes.read_only = read_only,
+            None => return Err(not_found()),
+        }
+        Ok(())
+    }
+    fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+        let mut fs = self.fs.borrow_mut();
+        let from = &fs.resolve(to_bytes(from.as_ref()), false)?;
+        let to = &fs.resolve(to_bytes(to.as_ref()), false)?;
+        fs.check_parent(from)?;
+        fs.check_parent(to)?;
+        check_path(to)?;
+        let is_dir = match fs.entity_map.get(from) {
+            Some(e) => matches!(e, Entity::Dir(..)),
+            None => return Err(not_found()),
+        };
+        if from == to {
+            return Ok(());
+        }
+        let prefix = [from, b"/".as_slice()].concat();
+        match (is_dir, fs.entity_map.get(to)) {
+            (false, Some(Entity::Dir(..))) => return Err(is_a_directory()),
+            (true, Some(Entity::File(_) | Entity::Symlink(..))) => return Err(not_a_directory()),
+            (true, Some(Entity::Dir(..))) if fs.has_children(to) => {
+                return Err(directory_not_empty())
+            }
+            (true, _) if to.starts_with(&prefix) => {
+                return Err(invalid_input("cannot move a directory into itself"))
+            }
+            _ => {}
+        }
+        let children: Vec<_> = fs
+            .entity_map
+            .range(prefix.clone()..)
+            .take_while(|(p, _)| p.starts_with(&prefix))
+            .map(|(p, _)| p.clone())
+            .collect();
+        for p in once(from.clone()).chain(children) {
+            let e = fs.entity_map.remove(&p).unwrap();
+            fs.entity_map.insert([to, &p[from.len()..]].concat(), e);
+        }
+        Ok(())
+    }
+    fn symlink(&self, original: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()> {
+        let mut fs = self.fs.borrow_mut();
+        let link = fs.resolve(to_bytes(link.as_ref()), false)?;
+        fs.check_parent(&link)?;
+        check_path(&link)?;
+        if fs.entity_map.contains_key(&link) {
+            return Err(already_exists());
+        }
+        let original = to_bytes(original.as_ref()).to_vec();
+        let attributes = Attributes::new(self.clock());
+        fs.entity_map
+            .insert(link, Entity::Symlink(original, attributes));
+        Ok(())
+    }
+    fn hard_link(&self, original: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()> {
+        let mut fs = self.fs.borrow_mut();
+        let original = fs.resolve(to_bytes(original.as_ref()), false)?;
+        let link = fs.resolve(to_bytes(link.as_ref()), false)?;
+        fs.check_parent(&link)?;
+        check_path(&link)?;
+        if fs.entity_map.contains_key(&link) {
+            return Err(already_exists());
+        }
+        let entity = match fs.entity_map.get(&original) {
+            Some(Entity::File(x)) => Entity::File(x.clone()),
+            Some(Entity::Symlink(target, attributes)) => {
+                Entity::Symlink(target.clone(), *attributes)
+            }
+            Some(Entity::Dir(..)) => {
+                return Err(permission_denied(
+                    "hard links to directories are not allowed",
+                ))
+            }
+            None => return Err(not_found()),
+        };
+        fs.entity_map.insert(link, entity);
+        Ok(())
+    }
+    fn read_link(&self, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let fs = self.fs.borrow();
+        match fs
+            .entity_map
+            .get(&fs.resolve(to_bytes(path.as_ref()), false)?)
+        {
+            Some(Entity::Symlink(target, _)) => Ok(to_path(target)),
+            Some(_) => Err(invalid_input("not a symbolic link")),
+            None => Err(not_found()),
+        }
+    }
+    fn sync_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut fs = self.fs.borrow_mut();
+        let dir = fs.resolve(to_bytes(path.as_ref()), true)?;
+        fs.check_dir(&dir)?;
+        fs.sync_dir(&dir);
+        Ok(())
+    }
+    fn canonicalize(&self, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let fs = self.fs.borrow();
+        let path = fs.resolve(to_bytes(path.as_ref()), true)?;
+        fs.metadata(&path)?;
+        Ok(to_path(&path))
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use std::io::{self, Seek, SeekFrom, Write};
+    use std::io::{self, IoSlice, IoSliceMut, Read, Seek, SeekFrom, Write};
+
+    use std::{
+        env::VarError,
+        ffi::CString,
+        future::Future,
+        path::{Path, PathBuf},
+        pin::pin,
+        task::{Context, Poll, Waker},
+        time::{Duration, UNIX_EPOCH},
+    };
 
-    use io_trait::{DirEntry, File, Io, Metadata};
+    use io_trait::{
+        AsyncFile, AsyncIo, AsyncOperation, DirEntry, File, FileType, Io, Metadata, OpenOptions,
+        OperationResult, Request,
+    };
     use wasm_bindgen_test::wasm_bindgen_test;
 
-    use crate::VirtualIo;
+    use crate::{Call, Crash, Fault, ShortIo, VecRef, VirtualAsyncIo, VirtualIo};
 
     #[wasm_bindgen_test]
     #[test]
@@ -341,8 +910,8 @@ mod test {
         }
         fn check_current_dir(
             io: &VirtualIo,
-            path: &str,
-            f: fn(x: &VirtualIo) -> io::Result<String>,
+            path: &Path,
+            f: fn(x: &VirtualIo) -> io::Result<PathBuf>,
         ) {
             assert_eq!(f(io).unwrap(), path);
         }
@@ -352,7 +921,7 @@ mod test {
         assert_eq!(result, "Hello, world!");
         check_len(&io.metadata("test.txt").unwrap(), Metadata::len, 13);
         // assert_eq!(io.metadata("test.txt").unwrap().len(), 13);
-        check_current_dir(&io, "", VirtualIo::current_dir);
+        check_current_dir(&io, Path::new(""), VirtualIo::current_dir);
         // assert_eq!(io.current_dir().unwrap(), "");
     }
 
@@ -367,6 +936,28 @@ mod test {
 
     #[wasm_bindgen_test]
     #[test]
+    fn test_env() {
+        let io = VirtualIo::with_env(&[], &[("HOME", "/home/a"), ("LANG", "C")]);
+        assert_eq!(io.var("HOME").unwrap(), "/home/a");
+        assert_eq!(io.var("PATH").unwrap_err(), VarError::NotPresent);
+        io.set_var("PATH", "/bin");
+        io.set_var("LANG", "en");
+        io.remove_var("HOME");
+        let x = io.vars().collect::<Vec<_>>();
+        assert_eq!(
+            x,
+            [
+                ("LANG".to_string(), "en".to_string()),
+                ("PATH".to_string(), "/bin".to_string())
+            ]
+        );
+        assert!(VirtualIo::new(&[]).vars().next().is_none());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    // `VecRef` writes the whole buffer, which the test relies on.
+    #[allow(clippy::unused_io_amount)]
     fn test_stdout() {
         {
             let io = VirtualIo::new(&[]);
@@ -382,6 +973,35 @@ mod test {
         }
     }
 
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_stdin() {
+        let mut io = VirtualIo::new(&[]);
+        io.stdin.write_all(b"Hello\nworld!").unwrap();
+        let mut s = io.stdin();
+        let mut buf = [0; 6];
+        s.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"Hello\n");
+        let mut rest = String::default();
+        io.stdin().read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "world!");
+        assert_eq!(s.read(&mut buf).unwrap(), 0);
+        io.stdin = VecRef::from(b"Bye!".as_slice());
+        rest.clear();
+        io.stdin().read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "Bye!");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_stderr() {
+        let io = VirtualIo::new(&[]);
+        io.stderr().write_all(b"error!").unwrap();
+        io.stdout().write_all(b"Hello").unwrap();
+        assert_eq!(io.stderr.to_stdout(), "error!");
+        assert_eq!(io.stdout.to_stdout(), "Hello");
+    }
+
     #[wasm_bindgen_test]
     #[test]
     fn test_write() {
@@ -400,8 +1020,8 @@ mod test {
         let i = VirtualIo::new(&[]);
         {
             let mut f = i.create("test.txt").unwrap();
-            f.write("Hello, world!".as_bytes()).unwrap();
-            f.write("?".as_bytes()).unwrap();
+            f.write_all("Hello, world!".as_bytes()).unwrap();
+            f.write_all("?".as_bytes()).unwrap();
             flush(&mut f, Write::flush);
             let m = f.metadata().unwrap();
             assert_eq!(m.len(), 14);
@@ -417,9 +1037,9 @@ mod test {
         let io = VirtualIo::new(&[]);
         {
             let mut f = io.create("test.txt").unwrap();
-            f.write("Hello, world!".as_bytes()).unwrap();
+            f.write_all("Hello, world!".as_bytes()).unwrap();
             f.seek(SeekFrom::Start(7)).unwrap();
-            f.write("there!".as_bytes()).unwrap();
+            f.write_all("there!".as_bytes()).unwrap();
             f.flush().unwrap();
             let m = f.metadata().unwrap();
             assert_eq!(m.len(), 13);
@@ -435,9 +1055,9 @@ mod test {
         let io = VirtualIo::new(&[]);
         {
             let mut f = io.create("test.txt").unwrap();
-            f.write("Hello, world!".as_bytes()).unwrap();
+            f.write_all("Hello, world!".as_bytes()).unwrap();
             f.seek(SeekFrom::Current(2)).unwrap();
-            f.write("there".as_bytes()).unwrap();
+            f.write_all("there".as_bytes()).unwrap();
             f.flush().unwrap();
             let m = f.metadata().unwrap();
             assert_eq!(m.len(), 20);
@@ -453,9 +1073,9 @@ mod test {
         let io = VirtualIo::new(&[]);
         {
             let mut f = io.create("test.txt").unwrap();
-            f.write("Hello, world!".as_bytes()).unwrap();
+            f.write_all("Hello, world!".as_bytes()).unwrap();
             f.seek(SeekFrom::End(-2)).unwrap();
-            f.write("there".as_bytes()).unwrap();
+            f.write_all("there".as_bytes()).unwrap();
             f.flush().unwrap();
             let m = f.metadata().unwrap();
             assert_eq!(m.len(), 16);
@@ -499,7 +1119,27 @@ mod test {
             .iter()
             .map(|v| v.path().to_owned())
             .collect::<Vec<_>>();
-        assert_eq!(x, ["a/b"].to_vec());
+        assert_eq!(x, [Path::new("a/b")].to_vec());
+        // paths are joined with `/` on all platforms, so they can be passed back.
+        assert_eq!(x[0].as_os_str(), "a/b");
+        io.create_dir_recursively("/c/d").unwrap();
+        assert!(io.metadata("c/d").unwrap().is_dir());
+        // the root isn't created as an entry of itself.
+        let x = io
+            .read_dir("")
+            .unwrap()
+            .map(|v| v.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(x, [Path::new("a"), Path::new("c")]);
+        assert_eq!(
+            io.create_dir("/").unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(
+            io.create_dir("e/f").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(io.metadata("e").is_err());
     }
 
     #[wasm_bindgen_test]
@@ -520,7 +1160,7 @@ mod test {
     }
 
     fn check_len(m: &super::Metadata, f: fn(m: &super::Metadata) -> u64, len: u64) {
-        assert_eq!(m.len(), len);
+        assert_eq!(f(m), len);
     }
 
     #[wasm_bindgen_test]
@@ -558,4 +1198,1186 @@ mod test {
         assert!(io.set_current_dir("").is_ok());
         assert!(io.set_current_dir("a").is_err());
     }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_remove_file() {
+        let io = VirtualIo::new(&[]);
+        io.write("test.txt", "Hello, world!".as_bytes()).unwrap();
+        io.create_dir("a").unwrap();
+        assert_eq!(
+            io.remove_file("a").unwrap_err().kind(),
+            io::ErrorKind::IsADirectory
+        );
+        io.remove_file("test.txt").unwrap();
+        assert!(io.open("test.txt").is_err());
+        assert_eq!(
+            io.remove_file("test.txt").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            io.remove_file("b/test.txt").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_remove_dir() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("a/b/test.txt", "Hello, world!".as_bytes())
+            .unwrap();
+        io.write("ab.txt", "Hello, world!".as_bytes()).unwrap();
+        assert_eq!(
+            io.remove_dir("a").unwrap_err().kind(),
+            io::ErrorKind::DirectoryNotEmpty
+        );
+        assert_eq!(
+            io.remove_dir("ab.txt").unwrap_err().kind(),
+            io::ErrorKind::NotADirectory
+        );
+        assert_eq!(
+            io.remove_dir("c").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        io.remove_file("a/b/test.txt").unwrap();
+        io.remove_dir("a/b").unwrap();
+        io.remove_dir("a").unwrap();
+        assert!(io.metadata("a").is_err());
+        assert!(io.metadata("ab.txt").is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_remove_dir_all() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("a/b/test.txt", "Hello, world!".as_bytes())
+            .unwrap();
+        io.write("a/test.txt", "Hello, world!".as_bytes()).unwrap();
+        io.write("ab.txt", "Hello, world!".as_bytes()).unwrap();
+        assert_eq!(
+            io.remove_dir_all("ab.txt").unwrap_err().kind(),
+            io::ErrorKind::NotADirectory
+        );
+        io.remove_dir_all("a").unwrap();
+        assert!(io.metadata("a").is_err());
+        assert!(io.metadata("a/b/test.txt").is_err());
+        assert!(io.metadata("ab.txt").is_ok());
+        assert_eq!(
+            io.remove_dir_all("a").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_rename() {
+        let io = VirtualIo::new(&[]);
+        io.write("a.txt", "Hello, world!".as_bytes()).unwrap();
+        io.write("b.txt", "Bye!".as_bytes()).unwrap();
+        io.rename("a.txt", "b.txt").unwrap();
+        assert!(io.metadata("a.txt").is_err());
+        assert_eq!(io.read_to_string("b.txt").unwrap(), "Hello, world!");
+        io.rename("b.txt", "b.txt").unwrap();
+        assert_eq!(io.read_to_string("b.txt").unwrap(), "Hello, world!");
+        assert_eq!(
+            io.rename("a.txt", "c.txt").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            io.rename("b.txt", "c/b.txt").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        io.create_dir("c").unwrap();
+        assert_eq!(
+            io.rename("b.txt", "c").unwrap_err().kind(),
+            io::ErrorKind::IsADirectory
+        );
+        io.rename("b.txt", "c/b.txt").unwrap();
+        assert_eq!(io.read_to_string("c/b.txt").unwrap(), "Hello, world!");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_rename_dir() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("a/b/c.txt", "Hello, world!".as_bytes())
+            .unwrap();
+        io.write("a/d.txt", "Bye!".as_bytes()).unwrap();
+        io.write("ab.txt", "ab".as_bytes()).unwrap();
+        io.create_dir("e").unwrap();
+        assert_eq!(
+            io.rename("a", "ab.txt").unwrap_err().kind(),
+            io::ErrorKind::NotADirectory
+        );
+        assert_eq!(
+            io.rename("a", "a/b/a").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        io.rename("a", "e").unwrap();
+        assert!(io.metadata("a").is_err());
+        assert!(io.metadata("a/b").is_err());
+        assert_eq!(io.read_to_string("e/b/c.txt").unwrap(), "Hello, world!");
+        assert_eq!(io.read_to_string("e/d.txt").unwrap(), "Bye!");
+        assert_eq!(io.read_to_string("ab.txt").unwrap(), "ab");
+        io.create_dir("a").unwrap();
+        io.create_dir("a/f").unwrap();
+        assert_eq!(
+            io.rename("e", "a").unwrap_err().kind(),
+            io::ErrorKind::DirectoryNotEmpty
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_write_atomic() {
+        let io = VirtualIo::new(&[]);
+        io.write_atomic("test.txt", "Hello, world!".as_bytes())
+            .unwrap();
+        assert_eq!(io.read_to_string("test.txt").unwrap(), "Hello, world!");
+        // an existing sibling isn't used as the temporary file.
+        io.write("test.txt.tmp", "sibling".as_bytes()).unwrap();
+        io.write_atomic("test.txt", "Bye!".as_bytes()).unwrap();
+        assert_eq!(io.read_to_string("test.txt").unwrap(), "Bye!");
+        assert_eq!(io.read_to_string("test.txt.tmp").unwrap(), "sibling");
+        assert_eq!(io.read_dir_vec("").unwrap().len(), 2);
+        io.create_dir("a").unwrap();
+        assert!(io.write_atomic("a", "Bye!".as_bytes()).is_err());
+        assert!(io.metadata("a").unwrap().is_dir());
+        assert_eq!(io.read_dir_vec("").unwrap().len(), 3);
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_open_with_append() {
+        let io = VirtualIo::new(&[]);
+        io.write("test.txt", "Hello".as_bytes()).unwrap();
+        {
+            let mut f = io
+                .open_with("test.txt", OpenOptions::new().append(true))
+                .unwrap();
+            f.seek(SeekFrom::Start(0)).unwrap();
+            f.write_all(", world!".as_bytes()).unwrap();
+        }
+        {
+            let mut f = io
+                .open_with("log.txt", OpenOptions::new().append(true).create(true))
+                .unwrap();
+            f.write_all("1".as_bytes()).unwrap();
+            f.write_all("2".as_bytes()).unwrap();
+        }
+        assert_eq!(io.read_to_string("test.txt").unwrap(), "Hello, world!");
+        assert_eq!(io.read_to_string("log.txt").unwrap(), "12");
+        assert_eq!(
+            io.open_with("x.txt", OpenOptions::new().append(true))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_open_with_read_write() {
+        let io = VirtualIo::new(&[]);
+        io.write("test.txt", "Hello, world!".as_bytes()).unwrap();
+        {
+            let mut f = io
+                .open_with("test.txt", OpenOptions::new().read(true).write(true))
+                .unwrap();
+            f.write_all("J".as_bytes()).unwrap();
+            let mut s = String::default();
+            f.read_to_string(&mut s).unwrap();
+            assert_eq!(s, "ello, world!");
+        }
+        assert_eq!(io.read_to_string("test.txt").unwrap(), "Jello, world!");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_open_with_create_new() {
+        let io = VirtualIo::new(&[]);
+        let options = {
+            let mut o = OpenOptions::new();
+            o.write(true).create_new(true);
+            o
+        };
+        io.open_with("test.txt", &options)
+            .unwrap()
+            .write_all("Hello, world!".as_bytes())
+            .unwrap();
+        assert_eq!(
+            io.open_with("test.txt", &options).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        io.create_dir("a").unwrap();
+        assert_eq!(
+            io.open_with("a", OpenOptions::new().read(true))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::IsADirectory
+        );
+        assert_eq!(
+            io.open_with("test.txt", OpenOptions::new().read(true).truncate(true))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_open_with_truncate() {
+        let io = VirtualIo::new(&[]);
+        io.write("test.txt", "Hello, world!".as_bytes()).unwrap();
+        let mut r = io.open("test.txt").unwrap();
+        io.open_with("test.txt", OpenOptions::new().write(true))
+            .unwrap()
+            .write_all("J".as_bytes())
+            .unwrap();
+        assert_eq!(io.read_to_string("test.txt").unwrap(), "Jello, world!");
+        io.open_with(
+            "test.txt",
+            OpenOptions::new().write(true).create(true).truncate(true),
+        )
+        .unwrap()
+        .write_all("Bye!".as_bytes())
+        .unwrap();
+        let mut s = String::default();
+        r.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "Bye!");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_access_mode() {
+        let io = VirtualIo::new(&[]);
+        io.write("test.txt", "Hello, world!".as_bytes()).unwrap();
+        assert_eq!(
+            io.open("test.txt")
+                .unwrap()
+                .write_all("Bye!".as_bytes())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::PermissionDenied
+        );
+        let mut s = String::default();
+        assert_eq!(
+            io.create("test.txt")
+                .unwrap()
+                .read_to_string(&mut s)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_metadata_times() {
+        let io = VirtualIo::new(&[]);
+        let t = |ms| UNIX_EPOCH + Duration::from_millis(ms);
+        let mut f = io.create("test.txt").unwrap();
+        io.now();
+        f.write_all("Hello, world!".as_bytes()).unwrap();
+        io.now();
+        io.create_dir("a").unwrap();
+        io.now();
+        io.read("test.txt").unwrap();
+        {
+            let m = io.metadata("test.txt").unwrap();
+            assert_eq!(m.file_type(), FileType::File);
+            assert!(m.is_file());
+            assert_eq!(m.created().unwrap(), t(0));
+            assert_eq!(m.modified().unwrap(), t(1));
+            assert_eq!(m.accessed().unwrap(), t(3));
+        }
+        {
+            let m = io.metadata("a").unwrap();
+            assert_eq!(m.file_type(), FileType::Dir);
+            assert_eq!(m.created().unwrap(), t(2));
+            assert_eq!(m.modified().unwrap(), t(2));
+        }
+        io.now();
+        io.create("test.txt").unwrap();
+        let m = f.metadata().unwrap();
+        assert_eq!(m.len(), 0);
+        assert_eq!(m.created().unwrap(), t(0));
+        assert_eq!(m.modified().unwrap(), t(4));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_read_only() {
+        let io = VirtualIo::new(&[]);
+        io.write("test.txt", "Hello, world!".as_bytes()).unwrap();
+        assert!(!io.metadata("test.txt").unwrap().is_read_only());
+        io.set_read_only("test.txt", true).unwrap();
+        assert!(io.metadata("test.txt").unwrap().is_read_only());
+        assert_eq!(
+            io.write("test.txt", "Bye!".as_bytes()).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+        assert_eq!(io.read_to_string("test.txt").unwrap(), "Hello, world!");
+        io.set_read_only("test.txt", false).unwrap();
+        io.write("test.txt", "Bye!".as_bytes()).unwrap();
+        assert_eq!(io.read_to_string("test.txt").unwrap(), "Bye!");
+        io.create_dir("a").unwrap();
+        io.set_read_only("a", true).unwrap();
+        assert!(io.metadata("a").unwrap().is_read_only());
+        assert!(io.set_read_only("b", true).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_create_dir_exists() {
+        let io = VirtualIo::new(&[]);
+        io.write("test.txt", "Hello, world!".as_bytes()).unwrap();
+        io.create_dir("a").unwrap();
+        assert_eq!(
+            io.create_dir("a").unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert!(io.create_dir("test.txt").is_err());
+        assert!(io.metadata("test.txt").unwrap().is_file());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_symlink() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("a/test.txt", "Hello, world!".as_bytes())
+            .unwrap();
+        io.symlink("test.txt", "a/link.txt").unwrap();
+        io.symlink("/a/test.txt", "link.txt").unwrap();
+        io.symlink("../a/test.txt", "a/up.txt").unwrap();
+        assert_eq!(io.read_to_string("a/link.txt").unwrap(), "Hello, world!");
+        assert_eq!(io.read_to_string("link.txt").unwrap(), "Hello, world!");
+        assert_eq!(io.read_to_string("a/up.txt").unwrap(), "Hello, world!");
+        assert_eq!(io.read_link("a/link.txt").unwrap(), Path::new("test.txt"));
+        assert_eq!(
+            io.read_link("a/test.txt").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(io.metadata("link.txt").unwrap().is_file());
+        let m = io.symlink_metadata("link.txt").unwrap();
+        assert!(m.is_symlink());
+        assert_eq!(m.len(), 11);
+        assert_eq!(
+            io.symlink("test.txt", "a/link.txt").unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        io.write("link.txt", "Bye!".as_bytes()).unwrap();
+        assert_eq!(io.read_to_string("a/test.txt").unwrap(), "Bye!");
+        io.remove_file("link.txt").unwrap();
+        assert!(io.metadata("link.txt").is_err());
+        assert!(io.metadata("a/test.txt").is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_symlink_dangling() {
+        let io = VirtualIo::new(&[]);
+        io.symlink("test.txt", "link.txt").unwrap();
+        assert_eq!(
+            io.metadata("link.txt").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(io.symlink_metadata("link.txt").unwrap().is_symlink());
+        let mut o = OpenOptions::new();
+        o.write(true).create_new(true);
+        assert_eq!(
+            io.open_with("link.txt", &o).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        io.write("link.txt", "Hello, world!".as_bytes()).unwrap();
+        assert_eq!(io.read_to_string("test.txt").unwrap(), "Hello, world!");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_symlink_dir() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("a/b/test.txt", "Hello, world!".as_bytes())
+            .unwrap();
+        io.symlink("a/b", "c").unwrap();
+        assert!(io.metadata("c").unwrap().is_dir());
+        assert!(io.metadata("c/").unwrap().is_dir());
+        assert_eq!(io.read_to_string("c/test.txt").unwrap(), "Hello, world!");
+        let x = io
+            .read_dir("c")
+            .unwrap()
+            .map(|v| v.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(x, [Path::new("c/test.txt")]);
+        io.write("c/new.txt", "new".as_bytes()).unwrap();
+        assert_eq!(io.read_to_string("a/b/new.txt").unwrap(), "new");
+        assert_eq!(
+            io.remove_dir("c").unwrap_err().kind(),
+            io::ErrorKind::NotADirectory
+        );
+        io.remove_dir_all("c").unwrap();
+        assert!(io.symlink_metadata("c").is_err());
+        assert!(io.metadata("a/b/test.txt").is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_symlink_loop() {
+        let io = VirtualIo::new(&[]);
+        io.symlink("b", "a").unwrap();
+        io.symlink("a", "b").unwrap();
+        io.symlink("c", "c").unwrap();
+        assert_eq!(io.metadata("a").unwrap_err().kind(), io::ErrorKind::Other);
+        assert!(io.read("c").is_err());
+        assert!(io.write("c/test.txt", "Hello".as_bytes()).is_err());
+        assert!(io.symlink_metadata("a").unwrap().is_symlink());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_hard_link() {
+        let io = VirtualIo::new(&[]);
+        io.write("a.txt", "Hello, world!".as_bytes()).unwrap();
+        io.hard_link("a.txt", "b.txt").unwrap();
+        io.open_with("b.txt", OpenOptions::new().append(true))
+            .unwrap()
+            .write_all("!".as_bytes())
+            .unwrap();
+        assert_eq!(io.read_to_string("a.txt").unwrap(), "Hello, world!!");
+        io.remove_file("a.txt").unwrap();
+        assert_eq!(io.read_to_string("b.txt").unwrap(), "Hello, world!!");
+        assert_eq!(
+            io.hard_link("a.txt", "c.txt").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            io.hard_link("b.txt", "b.txt").unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        io.create_dir("d").unwrap();
+        assert_eq!(
+            io.hard_link("d", "e").unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_odd_names() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("ди р/file name #1.txt", "Hello, world!".as_bytes())
+            .unwrap();
+        let x = io
+            .read_dir("ди р")
+            .unwrap()
+            .map(|v| v.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(x, [Path::new("ди р/file name #1.txt")]);
+        assert_eq!(
+            io.read_to_string("ди р/file name #1.txt").unwrap(),
+            "Hello, world!"
+        );
+        for name in ["a?", "a*", "a:b", "a|b", "a\\b", "a\0b"] {
+            assert_eq!(
+                io.write(name, "Hello".as_bytes()).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_non_utf8_names() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt, slice};
+        let io = VirtualIo::new(&[]);
+        let dir = Path::new(OsStr::from_bytes(b"\xff"));
+        let file = dir.join(OsStr::from_bytes(b"\xfe\x80.txt"));
+        io.create_dir(dir).unwrap();
+        io.write(&file, "Hello, world!".as_bytes()).unwrap();
+        io.symlink(&file, "link.txt").unwrap();
+        assert_eq!(io.read_link("link.txt").unwrap(), file);
+        assert_eq!(io.read_to_string("link.txt").unwrap(), "Hello, world!");
+        let x = io
+            .read_dir(dir)
+            .unwrap()
+            .map(|v| v.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(x, slice::from_ref(&file));
+        // names are matched lossily, but the original paths are returned.
+        assert_eq!(
+            io_trait::glob(&io, "*/\u{FFFD}*.txt").unwrap(),
+            slice::from_ref(&file)
+        );
+        assert_eq!(
+            io_trait::glob(&io, "**/*.txt").unwrap(),
+            [PathBuf::from("link.txt"), file]
+        );
+    }
+
+    fn walk(w: io_trait::WalkDir<'_, VirtualIo>) -> Vec<PathBuf> {
+        w.map(|e| e.unwrap().into_path()).collect()
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_walk_dir() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("r/b/c/x.txt", "x".as_bytes()).unwrap();
+        io.write_recursively("r/a.txt", "a".as_bytes()).unwrap();
+        io.write_recursively("r/b/y.txt", "y".as_bytes()).unwrap();
+        let p = |v: &[&str]| v.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(
+            walk(io.walk_dir("r").sort(true)),
+            p(&["r", "r/a.txt", "r/b", "r/b/c", "r/b/c/x.txt", "r/b/y.txt"])
+        );
+        assert_eq!(
+            walk(io.walk_dir("r").sort(true).contents_first(true)),
+            p(&["r/a.txt", "r/b/c/x.txt", "r/b/c", "r/b/y.txt", "r/b", "r"])
+        );
+        assert_eq!(
+            walk(io.walk_dir("r").sort(true).min_depth(1).max_depth(2)),
+            p(&["r/a.txt", "r/b", "r/b/c", "r/b/y.txt"])
+        );
+        assert_eq!(
+            walk(
+                io.walk_dir("r")
+                    .sort(true)
+                    .filter_entry(|e| e.path() != Path::new("r/b/c"))
+            ),
+            p(&["r", "r/a.txt", "r/b", "r/b/y.txt"])
+        );
+        let depths = io
+            .walk_dir("r/b/c")
+            .map(|e| e.unwrap().depth())
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [0, 1]);
+        assert_eq!(
+            io.walk_dir("none").next().unwrap().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_root_dir() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("d/x.txt", "x".as_bytes()).unwrap();
+        let p = |v: &[&str]| v.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert!(io.metadata("").unwrap().is_dir());
+        assert_eq!(io.canonicalize(".").unwrap(), PathBuf::default());
+        assert_eq!(
+            walk(io.walk_dir(".").sort(true)),
+            p(&[".", "./d", "./d/x.txt"])
+        );
+        assert_eq!(walk(io.walk_dir("").sort(true)), p(&["", "d", "d/x.txt"]));
+        assert_eq!(io_trait::glob(&io, "./d/*").unwrap(), p(&["./d/x.txt"]));
+        assert_eq!(
+            io_trait::glob(&io, "d/../d/*").unwrap(),
+            p(&["d/../d/x.txt"])
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_walk_dir_symlinks() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("r/a/x.txt", "x".as_bytes()).unwrap();
+        io.symlink("a", "r/l").unwrap();
+        io.symlink("..", "r/a/up").unwrap();
+        let p = |v: &[&str]| v.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(
+            walk(io.walk_dir("r").sort(true)),
+            p(&["r", "r/a", "r/a/up", "r/a/x.txt", "r/l"])
+        );
+        let x = io
+            .walk_dir("r")
+            .sort(true)
+            .follow_links(true)
+            .map(|e| e.map(|e| e.into_path()).map_err(|e| e.kind()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            x,
+            [
+                Ok(PathBuf::from("r")),
+                Ok(PathBuf::from("r/a")),
+                Err(io::ErrorKind::Other),
+                Ok(PathBuf::from("r/a/x.txt")),
+                Ok(PathBuf::from("r/l")),
+                Err(io::ErrorKind::Other),
+                Ok(PathBuf::from("r/l/x.txt")),
+            ]
+        );
+        let x = io
+            .walk_dir("r")
+            .sort(true)
+            .filter_entry(|e| !e.metadata().is_symlink())
+            .count();
+        assert_eq!(x, 3);
+        assert_eq!(io.canonicalize("r/l/up/a").unwrap(), Path::new("r/a"));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_glob() {
+        let io = VirtualIo::new(&[]);
+        for f in [
+            "cas/a.blk",
+            "cas/x/b.blk",
+            "cas/x/y/c.blk",
+            "cas/x/d.txt",
+            "e.blk",
+        ] {
+            io.write_recursively(f, "data".as_bytes()).unwrap();
+        }
+        io.symlink("..", "cas/x/loop").unwrap();
+        let p = |v: &[&str]| v.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let glob = |pattern| io_trait::glob(&io, pattern).unwrap();
+        assert_eq!(
+            glob("cas/**/*.blk"),
+            p(&["cas/a.blk", "cas/x/b.blk", "cas/x/y/c.blk"])
+        );
+        assert_eq!(glob("*.blk"), p(&["e.blk"]));
+        assert_eq!(glob("*"), p(&["cas", "e.blk"]));
+        assert_eq!(glob("cas/?/[a-c].*"), p(&["cas/x/b.blk"]));
+        assert_eq!(
+            glob("cas/x/[!b]*"),
+            p(&["cas/x/d.txt", "cas/x/loop", "cas/x/y"])
+        );
+        assert_eq!(glob("cas/x/loop/a.blk"), p(&["cas/x/loop/a.blk"]));
+        assert_eq!(glob("cas/x/loop/*.blk"), p(&["cas/x/loop/a.blk"]));
+        assert_eq!(
+            glob("cas/x/**"),
+            p(&[
+                "cas/x",
+                "cas/x/b.blk",
+                "cas/x/d.txt",
+                "cas/x/loop",
+                "cas/x/y",
+                "cas/x/y/c.blk"
+            ])
+        );
+        assert!(glob("none/*").is_empty());
+        // `..` goes up instead of being dropped.
+        assert_eq!(glob("cas/x/../*.blk"), p(&["cas/x/../a.blk"]));
+        assert!(glob("cas/../x/*").is_empty());
+        assert_eq!(
+            io_trait::glob(&io, "[a").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_read_dir_lazy() {
+        let io = VirtualIo::new(&[]);
+        for f in ["d/a/x.txt", "d/a-b.txt", "d/a0.txt", "d/b/c/y.txt", "e.txt"] {
+            io.write_recursively(f, "data".as_bytes()).unwrap();
+        }
+        let names = |path| {
+            io.read_dir(path)
+                .unwrap()
+                .map(|v| v.unwrap().path())
+                .collect::<Vec<_>>()
+        };
+        let p = |v: &[&str]| v.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(names("d"), p(&["d/a", "d/a-b.txt", "d/a0.txt", "d/b"]));
+        assert_eq!(names(""), p(&["d", "e.txt"]));
+        assert_eq!(io.read_dir_vec("d/b").unwrap().len(), 1);
+        let mut x = io.read_dir("d").unwrap();
+        assert_eq!(x.next().unwrap().unwrap().path(), Path::new("d/a"));
+        io.remove_file("d/a0.txt").unwrap();
+        io.write("d/c.txt", "data".as_bytes()).unwrap();
+        let rest = x.map(|v| v.unwrap().path()).collect::<Vec<_>>();
+        assert_eq!(rest, p(&["d/a-b.txt", "d/b", "d/c.txt"]));
+        assert_eq!(
+            io.read_dir("e.txt").err().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_sync() {
+        let io = VirtualIo::new(&[]);
+        let mut f = io.create("test.txt").unwrap();
+        f.write_all("Hello".as_bytes()).unwrap();
+        assert_eq!(io.sync_count("test.txt").unwrap(), 0);
+        assert!(io.synced_data("test.txt").unwrap().is_empty());
+        f.sync_data().unwrap();
+        f.write_all(", world!".as_bytes()).unwrap();
+        assert_eq!(io.synced_data("test.txt").unwrap(), b"Hello");
+        f.sync_all().unwrap();
+        assert_eq!(io.sync_count("test.txt").unwrap(), 2);
+        assert_eq!(io.synced_data("test.txt").unwrap(), b"Hello, world!");
+        io.write_atomic("a.txt", "atomic".as_bytes()).unwrap();
+        assert_eq!(io.synced_data("a.txt").unwrap(), b"atomic");
+        assert_eq!(
+            io.sync_count("none.txt").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_set_len() {
+        let io = VirtualIo::new(&[]);
+        let mut f = io.create("test.txt").unwrap();
+        f.write_all("Hello, world!".as_bytes()).unwrap();
+        f.set_len(5).unwrap();
+        assert_eq!(io.read("test.txt").unwrap(), b"Hello");
+        f.set_len(7).unwrap();
+        assert_eq!(io.read("test.txt").unwrap(), b"Hello\0\0");
+        assert_eq!(f.stream_position().unwrap(), 13);
+        f.write_all("!".as_bytes()).unwrap();
+        assert_eq!(io.read("test.txt").unwrap(), b"Hello\0\0\0\0\0\0\0\0!");
+        assert_eq!(
+            io.open("test.txt").unwrap().set_len(0).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_crash_discard() {
+        let io = VirtualIo::new(&[]);
+        io.create_dir("d").unwrap();
+        io.write_atomic("d/a.txt", "synced".as_bytes()).unwrap();
+        io.write("d/old.txt", "old".as_bytes()).unwrap();
+        io.sync_dir("d").unwrap();
+        io.sync_dir("").unwrap();
+        io.open_with("d/a.txt", OpenOptions::new().append(true))
+            .unwrap()
+            .write_all(" lost".as_bytes())
+            .unwrap();
+        io.write("d/b.txt", "lost".as_bytes()).unwrap();
+        io.remove_file("d/old.txt").unwrap();
+        io.create_dir("e").unwrap();
+        io.crash(Crash::Discard);
+        assert_eq!(io.read_to_string("d/a.txt").unwrap(), "synced");
+        assert!(io.metadata("d/b.txt").is_err());
+        assert!(io.metadata("e").is_err());
+        // the file is back, but its data was never synced.
+        assert_eq!(io.read_to_string("d/old.txt").unwrap(), "");
+        let x = io.read_dir_vec("d").unwrap().len();
+        assert_eq!(x, 2);
+        // everything that survived a crash is durable.
+        io.crash(Crash::Discard);
+        assert_eq!(io.read_to_string("d/a.txt").unwrap(), "synced");
+        assert!(io.metadata("d/old.txt").is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_crash_rename_dir() {
+        let io = VirtualIo::new(&[]);
+        io.create_dir("a").unwrap();
+        io.write("a/x.txt", "x".as_bytes()).unwrap();
+        io.open("a/x.txt").unwrap().sync_all().unwrap();
+        io.sync_dir("a").unwrap();
+        io.sync_dir("").unwrap();
+        io.rename("a", "b").unwrap();
+        io.crash(Crash::Discard);
+        assert!(io.metadata("b").is_err());
+        assert_eq!(io.read_to_string("a/x.txt").unwrap(), "x");
+        io.rename("a", "b").unwrap();
+        io.sync_dir("").unwrap();
+        io.crash(Crash::Discard);
+        assert!(io.metadata("a").is_err());
+        assert_eq!(io.read_to_string("b/x.txt").unwrap(), "x");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_crash_tear() {
+        let run = |seed| {
+            let io = VirtualIo::new(&[]);
+            io.write("a.bin", &[1; 1024]).unwrap();
+            io.open("a.bin").unwrap().sync_all().unwrap();
+            io.sync_dir("").unwrap();
+            io.write("a.bin", &[2; 2048]).unwrap();
+            io.write("b.bin", &[3; 10]).unwrap();
+            io.crash(Crash::Tear { seed });
+            (io.read("a.bin").unwrap(), io.metadata("b.bin").is_ok())
+        };
+        let mut lengths = Vec::default();
+        let mut survived = Vec::default();
+        for seed in 0..32 {
+            let (a, b) = run(seed);
+            assert_eq!((a.clone(), b), run(seed));
+            assert!(a.len() == 1024 || a.len() == 2048);
+            for block in a.chunks(512) {
+                assert!(block.iter().all(|&c| c == block[0]));
+            }
+            lengths.push(a.len());
+            survived.push(b);
+        }
+        assert!(lengths.contains(&1024) && lengths.contains(&2048));
+        assert!(survived.contains(&true) && survived.contains(&false));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_fault_nth() {
+        let io = VirtualIo::new(&[]);
+        io.faults
+            .borrow_mut()
+            .add(Fault::new(Call::Write, io::ErrorKind::StorageFull).nth(2));
+        io.write("a.txt", "a".as_bytes()).unwrap();
+        assert_eq!(
+            io.write("b.txt", "b".as_bytes()).unwrap_err().kind(),
+            io::ErrorKind::StorageFull
+        );
+        assert_eq!(io.read("b.txt").unwrap(), b"");
+        io.write("c.txt", "c".as_bytes()).unwrap();
+        io.faults
+            .borrow_mut()
+            .add(Fault::new(Call::Open, io::ErrorKind::PermissionDenied));
+        assert_eq!(
+            io.read("a.txt").unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+        io.faults.borrow_mut().clear();
+        assert_eq!(io.read("a.txt").unwrap(), b"a");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_fault_path() {
+        let io = VirtualIo::new(&[]);
+        io.write_recursively("cas/x/a.blk", "a".as_bytes()).unwrap();
+        io.faults.borrow_mut().add(
+            Fault::new(Call::Create, io::ErrorKind::PermissionDenied)
+                .path(io_trait::Pattern::new("cas/**/*.blk").unwrap()),
+        );
+        assert_eq!(
+            io.write("./cas/x/b.blk", "b".as_bytes())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::PermissionDenied
+        );
+        assert!(io.metadata("cas/x/b.blk").is_err());
+        io.write("cas/x/b.txt", "b".as_bytes()).unwrap();
+        assert_eq!(io.read("cas/x/a.blk").unwrap(), b"a");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_fault_interrupted() {
+        let io = VirtualIo::new(&[]);
+        io.faults
+            .borrow_mut()
+            .add(Fault::new(Call::Write, io::ErrorKind::Interrupted).nth(1))
+            .add(Fault::new(Call::Write, io::ErrorKind::Interrupted).nth(2));
+        let mut f = io.create("a.txt").unwrap();
+        assert_eq!(
+            f.write("Hello".as_bytes()).unwrap_err().kind(),
+            io::ErrorKind::Interrupted
+        );
+        // `write_all` retries interrupted writes.
+        f.write_all("Hello".as_bytes()).unwrap();
+        assert_eq!(io.read("a.txt").unwrap(), b"Hello");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_short_io_fixed() {
+        let io = VirtualIo::new(&[]);
+        io.faults.borrow_mut().short_io(ShortIo::fixed(3));
+        let mut f = io.create("a.txt").unwrap();
+        assert_eq!(f.write("Hello".as_bytes()).unwrap(), 3);
+        f.write_all("lo, world!".as_bytes()).unwrap();
+        let mut f = io.open("a.txt").unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(f.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"Hel");
+        assert_eq!(io.read_to_string("a.txt").unwrap(), "Hello, world!");
+        io.faults.borrow_mut().clear();
+        assert_eq!(io.open("a.txt").unwrap().read(&mut buf).unwrap(), 8);
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_short_io_random() {
+        let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let run = |seed| {
+            let io = VirtualIo::new(&[]);
+            io.faults
+                .borrow_mut()
+                .short_io(ShortIo::random(seed).interrupt(2));
+            let mut f = io.create("a.bin").unwrap();
+            let mut lens = Vec::default();
+            let mut interrupted = 0;
+            let mut rest = data.as_slice();
+            while !rest.is_empty() {
+                match f.write(rest) {
+                    Ok(len) => {
+                        assert!(len > 0 && len <= rest.len());
+                        lens.push(len);
+                        rest = &rest[len..];
+                    }
+                    Err(e) => {
+                        assert_eq!(e.kind(), io::ErrorKind::Interrupted);
+                        interrupted += 1;
+                    }
+                }
+            }
+            assert_eq!(io.read("a.bin").unwrap(), data);
+            (lens, interrupted)
+        };
+        let (lens, interrupted) = run(7);
+        assert!(lens.len() > 1);
+        assert!(interrupted > 0);
+        assert_eq!(run(7), (lens, interrupted));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_async_io() {
+        let io = VirtualIo::new(&[]);
+        let aio = io.async_io().pending_polls(2);
+        let path = CString::new("a.txt").unwrap();
+        let file = aio.create(&path).unwrap();
+        {
+            let mut operation = file.write(0, b"Hello").unwrap();
+            assert!(matches!(operation.get_result(), OperationResult::Pending));
+            assert!(matches!(operation.get_result(), OperationResult::Pending));
+            // the data is transferred when the operation completes.
+            assert_eq!(io.read("a.txt").unwrap(), b"");
+            assert!(matches!(operation.get_result(), OperationResult::Ok(5)));
+            assert!(matches!(operation.get_result(), OperationResult::Ok(5)));
+        }
+        {
+            // a dropped pending operation has no effect.
+            let mut operation = file.write(5, b", world!").unwrap();
+            assert!(matches!(operation.get_result(), OperationResult::Pending));
+        }
+        assert_eq!(io.read("a.txt").unwrap(), b"Hello");
+        // a file created for writing can't be read.
+        let mut buffer = [0; 16];
+        let mut operation = file.read(0, &mut buffer).unwrap();
+        operation.get_result();
+        operation.get_result();
+        match operation.get_result() {
+            OperationResult::Err(e) => assert_eq!(e.kind(), io::ErrorKind::PermissionDenied),
+            _ => panic!(),
+        }
+        //
+        let aio = io.async_io();
+        let file = aio.open(&path).unwrap();
+        let mut operation = file.read(1, &mut buffer).unwrap();
+        assert!(matches!(operation.get_result(), OperationResult::Ok(4)));
+        drop(operation);
+        assert_eq!(&buffer[..4], b"ello");
+        assert_eq!(
+            aio.open(&CString::new("b.txt").unwrap())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_async_io_future() {
+        let io = VirtualIo::new(&[]);
+        io.faults.borrow_mut().short_io(ShortIo::fixed(3));
+        let aio = io.async_io().pending_polls(4);
+        let file = aio.create(&CString::new("a.txt").unwrap()).unwrap();
+        let mut future = pin!(file.write(0, b"Hello").unwrap().future());
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut polls = 0;
+        let result = loop {
+            polls += 1;
+            if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+                break result;
+            }
+        };
+        assert_eq!(result.unwrap(), 3);
+        // a pending future checks the operation again after setting the waker.
+        assert_eq!(polls, 3);
+        assert_eq!(io.read("a.txt").unwrap(), b"Hel");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_async_io_open_with() {
+        let io = VirtualIo::new(&[]);
+        io.write("a.txt", b"Hello").unwrap();
+        let aio = io.async_io();
+        let path = CString::new("a.txt").unwrap();
+        let options = OpenOptions::new().read(true).write(true).clone();
+        let file = aio.open_with(&path, &options).unwrap();
+        assert!(matches!(
+            file.write(0, b"J").unwrap().get_result(),
+            OperationResult::Ok(1)
+        ));
+        let mut buffer = [0; 8];
+        assert!(matches!(
+            file.read(0, &mut buffer).unwrap().get_result(),
+            OperationResult::Ok(5)
+        ));
+        assert_eq!(&buffer[..5], b"Jello");
+        let file = aio
+            .open_with(&path, OpenOptions::new().append(true))
+            .unwrap();
+        assert!(matches!(
+            file.write(0, b"!").unwrap().get_result(),
+            OperationResult::Ok(1)
+        ));
+        assert_eq!(io.read("a.txt").unwrap(), b"Jello!");
+        let options = OpenOptions::new().write(true).create_new(true).clone();
+        assert_eq!(
+            aio.open_with(&path, &options).err().unwrap().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_async_io_concurrent() {
+        let io = VirtualIo::new(&[]);
+        io.write("a.txt", b"Hello, world!").unwrap();
+        let file = io
+            .async_io()
+            .pending_polls(1)
+            .open_with(
+                &CString::new("a.txt").unwrap(),
+                OpenOptions::new().read(true).write(true),
+            )
+            .unwrap();
+        let (mut a, mut b) = ([0; 5], [0; 5]);
+        let mut read_a = file.read(0, &mut a).unwrap();
+        let mut write = file.write(7, b"World").unwrap();
+        let mut read_b = file.read(7, &mut b).unwrap();
+        assert!(matches!(read_b.get_result(), OperationResult::Pending));
+        // operations complete in the order they are polled.
+        assert!(matches!(read_b.get_result(), OperationResult::Ok(5)));
+        assert!(matches!(write.get_result(), OperationResult::Pending));
+        assert!(matches!(write.get_result(), OperationResult::Ok(5)));
+        assert!(matches!(read_a.get_result(), OperationResult::Pending));
+        assert!(matches!(read_a.get_result(), OperationResult::Ok(5)));
+        drop((read_a, write, read_b));
+        assert_eq!(&a, b"Hello");
+        assert_eq!(&b, b"world");
+        assert_eq!(io.read("a.txt").unwrap(), b"Hello, World!");
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_async_io_vectored() {
+        let io = VirtualIo::new(&[]);
+        let aio = io.async_io();
+        let path = CString::new("a.bin").unwrap();
+        let file = aio.create(&path).unwrap();
+        let buffers = [IoSlice::new(b"head"), IoSlice::new(b"payload")];
+        let mut operation = file.write_vectored(2, &buffers).unwrap();
+        assert!(matches!(operation.get_result(), OperationResult::Ok(11)));
+        assert_eq!(io.read("a.bin").unwrap(), b"\0\0headpayload");
+        // the transfer stops at the first short write.
+        io.faults.borrow_mut().short_io(ShortIo::fixed(3));
+        let mut operation = file.write_vectored(0, &buffers).unwrap();
+        assert!(matches!(operation.get_result(), OperationResult::Ok(3)));
+        io.faults.borrow_mut().clear();
+        assert_eq!(io.read("a.bin").unwrap(), b"heaeadpayload");
+        let file = aio.open(&path).unwrap();
+        let (mut a, mut b, mut c) = ([0; 4], [0; 8], [0; 8]);
+        let mut buffers = [
+            IoSliceMut::new(&mut a),
+            IoSliceMut::new(&mut b),
+            IoSliceMut::new(&mut c),
+        ];
+        let mut operation = file.read_vectored(3, &mut buffers).unwrap();
+        // the read stops at the end of the file.
+        assert!(matches!(operation.get_result(), OperationResult::Ok(10)));
+        drop(operation);
+        assert_eq!((&a, &b), (b"eadp", b"ayload\0\0"));
+        assert_eq!(c, [0; 8]);
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_async_io_batch() {
+        let io = VirtualIo::new(&[]);
+        io.write("a.txt", b"Hello, world!").unwrap();
+        let aio = io.async_io().pending_polls(2);
+        let file = aio
+            .open_with(
+                &CString::new("a.txt").unwrap(),
+                OpenOptions::new().read(true).write(true),
+            )
+            .unwrap();
+        let mut a = [0; 5];
+        let requests = vec![
+            Request::Read {
+                file: &file,
+                offset: 0,
+                buffer: &mut a,
+            },
+            Request::Write {
+                file: &file,
+                offset: 7,
+                buffer: b"World",
+            },
+        ];
+        let mut operations = aio.submit(requests).unwrap();
+        // the first operation completes first because it's polled first.
+        assert!(matches!(aio.wait_any(&mut operations), Some((0, Ok(5)))));
+        let results = aio.wait_all(&mut operations);
+        assert_eq!(
+            results.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(),
+            [5, 5]
+        );
+        drop(operations);
+        assert_eq!(&a, b"Hello");
+        assert_eq!(io.read("a.txt").unwrap(), b"Hello, World!");
+        assert!(aio.wait_any(&mut []).is_none());
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_async_io_wait() {
+        let io = VirtualIo::new(&[]);
+        io.write("a.txt", b"Hello").unwrap();
+        let aio = io.async_io().pending_polls(3);
+        let file = aio.open(&CString::new("a.txt").unwrap()).unwrap();
+        let mut buffer = [0; 8];
+        let mut operation = file.read(0, &mut buffer).unwrap();
+        assert!(matches!(operation.get_result(), OperationResult::Pending));
+        // each of the two remaining polls takes one virtual tick.
+        let tick = VirtualAsyncIo::POLL_TIME;
+        let result = operation.wait(Some(Duration::ZERO));
+        assert!(matches!(result, OperationResult::Pending));
+        let result = operation.wait(Some(tick));
+        assert!(matches!(result, OperationResult::Pending));
+        let result = operation.wait(Some(tick));
+        assert!(matches!(result, OperationResult::Ok(5)));
+        drop(operation);
+        assert_eq!(&buffer[..5], b"Hello");
+        let mut operation = file.read(0, &mut buffer).unwrap();
+        assert!(matches!(
+            operation.wait(Some(tick * 3)),
+            OperationResult::Ok(5)
+        ));
+    }
+
+    #[wasm_bindgen_test]
+    #[test]
+    fn test_async_io_cancel() {
+        let io = VirtualIo::new(&[]);
+        let aio = io.async_io().pending_polls(1);
+        let file = aio.create(&CString::new("a.txt").unwrap()).unwrap();
+        let mut operation = file.write(0, b"Hello").unwrap();
+        assert!(operation.cancel());
+        assert!(matches!(operation.get_result(), OperationResult::Err(_)));
+        assert!(!operation.cancel());
+        drop(operation);
+        assert_eq!(io.read("a.txt").unwrap(), b"");
+        let mut operation = file.write(0, b"Hello").unwrap();
+        assert!(matches!(operation.wait(None), OperationResult::Ok(5)));
+        assert!(!operation.cancel());
+        assert!(matches!(operation.get_result(), OperationResult::Ok(5)));
+        drop(operation);
+        assert_eq!(io.read("a.txt").unwrap(), b"Hello");
+    }
 }
@@ -1,4 +1,14 @@
-use std::{ffi::CStr, io};
+use std::{
+    ffi::CStr,
+    future::Future,
+    io::{self, IoSlice, IoSliceMut},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    thread::yield_now,
+    time::{Duration, Instant},
+};
+
+use crate::OpenOptions;
 
 #[derive(Debug)]
 pub enum OperationResult {
@@ -7,21 +17,188 @@ pub enum OperationResult {
     Err(io::Error),
 }
 
+impl OperationResult {
+    /// Returns the result of a completed operation or `None` if it's pending.
+    pub fn ready(self) -> Option<io::Result<usize>> {
+        match self {
+            OperationResult::Ok(len) => Some(Ok(len)),
+            OperationResult::Pending => None,
+            OperationResult::Err(e) => Some(Err(e)),
+        }
+    }
+}
+
 pub trait AsyncOperation {
     fn get_result(&mut self) -> OperationResult;
+    /// Arranges for `waker` to be woken when the operation completes. The default
+    /// implementation wakes it immediately, so the operation is polled again.
+    fn set_waker(&mut self, waker: &Waker) {
+        waker.wake_by_ref();
+    }
+    /// Blocks until the operation completes or `timeout` elapses and returns its result,
+    /// [`OperationResult::Pending`] if it has timed out. `None` waits without a limit. The
+    /// default implementation polls the operation.
+    fn wait(&mut self, timeout: Option<Duration>) -> OperationResult {
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        loop {
+            let result = self.get_result();
+            if !matches!(result, OperationResult::Pending)
+                || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return result;
+            }
+            yield_now();
+        }
+    }
+    /// Cancels the operation and blocks until it no longer uses its buffer. Returns `true` if
+    /// it has been canceled and `false` if it has completed before, then
+    /// [`AsyncOperation::get_result`] returns the result. The default implementation can't
+    /// cancel, so it waits until the operation completes.
+    fn cancel(&mut self) -> bool {
+        self.wait(None);
+        false
+    }
+    /// Returns a future that resolves to the number of transferred bytes.
+    fn future(self) -> OperationFuture<Self>
+    where
+        Self: Sized,
+    {
+        OperationFuture(self)
+    }
 }
 
+/// A [`Future`] created by [`AsyncOperation::future`].
+pub struct OperationFuture<T>(T);
+
+impl<T: AsyncOperation + Unpin> Future for OperationFuture<T> {
+    type Output = io::Result<usize>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let operation = &mut self.0;
+        let result = match operation.get_result() {
+            OperationResult::Pending => {
+                operation.set_waker(cx.waker());
+                // the operation could complete before the waker is set.
+                operation.get_result()
+            }
+            result => result,
+        };
+        match result.ready() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// A file for positional asynchronous I/O. Several operations can be in flight at once,
+/// each one borrows its buffer until it's dropped. Dropping an operation that is still in
+/// progress cancels it.
 pub trait AsyncFile {
     type Operation<'a>: AsyncOperation
     where
         Self: 'a;
-    fn read<'a>(&'a mut self, offset: u64, buffer: &'a mut [u8])
-        -> io::Result<Self::Operation<'a>>;
-    fn write<'a>(&'a mut self, offset: u64, buffer: &'a [u8]) -> io::Result<Self::Operation<'a>>;
+    fn read<'a>(&'a self, offset: u64, buffer: &'a mut [u8]) -> io::Result<Self::Operation<'a>>;
+    /// If the file is opened in append mode, `buffer` is written at the end of the file and
+    /// `offset` is ignored.
+    fn write<'a>(&'a self, offset: u64, buffer: &'a [u8]) -> io::Result<Self::Operation<'a>>;
+    /// Reads into `buffers` in order, starting at `offset`. Like
+    /// [`std::io::Read::read_vectored`], the operation stops at the first buffer that isn't
+    /// filled completely.
+    fn read_vectored<'a>(
+        &'a self,
+        offset: u64,
+        buffers: &'a mut [IoSliceMut<'a>],
+    ) -> io::Result<Self::Operation<'a>>;
+    /// Writes `buffers` in order, starting at `offset`, see [`AsyncFile::read_vectored`]. In
+    /// append mode, they are written together at the end of the file, like [`AsyncFile::write`].
+    fn write_vectored<'a>(
+        &'a self,
+        offset: u64,
+        buffers: &'a [IoSlice<'a>],
+    ) -> io::Result<Self::Operation<'a>>;
 }
 
 pub trait AsyncIo {
     type File: AsyncFile;
-    fn create(&self, path: &CStr) -> io::Result<Self::File>;
-    fn open(&self, path: &CStr) -> io::Result<Self::File>;
+    /// Opens a file with the same options as [`crate::Io::open_with`].
+    fn open_with(&self, path: &CStr, options: &OpenOptions) -> io::Result<Self::File>;
+    /// Creates or truncates a file for writing.
+    fn create(&self, path: &CStr) -> io::Result<Self::File> {
+        self.open_with(
+            path,
+            OpenOptions::new().write(true).create(true).truncate(true),
+        )
+    }
+    /// Opens an existing file for reading.
+    fn open(&self, path: &CStr) -> io::Result<Self::File> {
+        self.open_with(path, OpenOptions::new().read(true))
+    }
+    /// Submits several operations at once. If one of them can't be submitted, the others
+    /// are canceled and the error is returned.
+    fn submit<'a>(
+        &self,
+        requests: Vec<Request<'a, Self::File>>,
+    ) -> io::Result<Vec<<Self::File as AsyncFile>::Operation<'a>>> {
+        requests
+            .into_iter()
+            .map(|request| match request {
+                Request::Read {
+                    file,
+                    offset,
+                    buffer,
+                } => file.read(offset, buffer),
+                Request::Write {
+                    file,
+                    offset,
+                    buffer,
+                } => file.write(offset, buffer),
+            })
+            .collect()
+    }
+    /// Blocks until one of `operations` completes and returns its index and result, or
+    /// `None` if there are no operations.
+    fn wait_any(
+        &self,
+        operations: &mut [<Self::File as AsyncFile>::Operation<'_>],
+    ) -> Option<(usize, io::Result<usize>)> {
+        if operations.is_empty() {
+            return None;
+        }
+        loop {
+            for (i, operation) in operations.iter_mut().enumerate() {
+                if let Some(result) = operation.get_result().ready() {
+                    return Some((i, result));
+                }
+            }
+            yield_now();
+        }
+    }
+    /// Blocks until all `operations` complete and returns their results.
+    fn wait_all(
+        &self,
+        operations: &mut [<Self::File as AsyncFile>::Operation<'_>],
+    ) -> Vec<io::Result<usize>> {
+        operations
+            .iter_mut()
+            .map(|operation| loop {
+                if let Some(result) = operation.get_result().ready() {
+                    break result;
+                }
+                yield_now();
+            })
+            .collect()
+    }
+}
+
+/// An operation for [`AsyncIo::submit`].
+pub enum Request<'a, F> {
+    Read {
+        file: &'a F,
+        offset: u64,
+        buffer: &'a mut [u8],
+    },
+    Write {
+        file: &'a F,
+        offset: u64,
+        buffer: &'a [u8],
+    },
 }
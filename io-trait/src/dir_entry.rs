@@ -1,17 +1,17 @@
-use std::{fs, io};
+use std::{fs, io, path::PathBuf};
 
 use crate::Metadata;
 
 pub trait DirEntry {
     type Metadata: Metadata;
-    fn path(&self) -> String;
+    fn path(&self) -> PathBuf;
     fn metadata(&self) -> io::Result<Self::Metadata>;
 }
 
 impl DirEntry for fs::DirEntry {
     type Metadata = fs::Metadata;
-    fn path(&self) -> String {
-        self.path().to_str().unwrap().to_string()
+    fn path(&self) -> PathBuf {
+        self.path()
     }
     fn metadata(&self) -> io::Result<Self::Metadata> {
         self.metadata()
@@ -29,7 +29,7 @@ mod test {
         let x = fs::read_dir(".").unwrap();
         for i in x {
             let i = i.unwrap();
-            assert_eq!(DirEntry::path(&i), i.path().to_str().unwrap());
+            assert_eq!(DirEntry::path(&i), i.path());
             assert_eq!(
                 DirEntry::metadata(&i).unwrap().is_dir(),
                 i.metadata().unwrap().is_dir()
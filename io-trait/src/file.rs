@@ -9,6 +9,12 @@ use crate::Metadata;
 pub trait File: Read + Write + Seek + fmt::Debug {
     type Metadata: Metadata;
     fn metadata(&self) -> io::Result<Self::Metadata>;
+    /// Flushes data and metadata to the storage device, see [`fs::File::sync_all`].
+    fn sync_all(&self) -> io::Result<()>;
+    /// Same as [`File::sync_all`] but may skip metadata that isn't needed to read the data.
+    fn sync_data(&self) -> io::Result<()>;
+    /// Truncates or extends the file with zeros. The cursor position isn't changed.
+    fn set_len(&self, size: u64) -> io::Result<()>;
 }
 
 impl File for fs::File {
@@ -16,4 +22,13 @@ impl File for fs::File {
     fn metadata(&self) -> io::Result<Self::Metadata> {
         fs::File::metadata(self)
     }
+    fn sync_all(&self) -> io::Result<()> {
+        fs::File::sync_all(self)
+    }
+    fn sync_data(&self) -> io::Result<()> {
+        fs::File::sync_data(self)
+    }
+    fn set_len(&self, size: u64) -> io::Result<()> {
+        fs::File::set_len(self, size)
+    }
 }
@@ -0,0 +1,350 @@
+use std::{
+    ffi::OsStr,
+    io,
+    path::{Component as PathComponent, Path, PathBuf},
+};
+
+use crate::{DirEntry, Io, Metadata};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnySeq,
+    /// `[a-z]`, `[!a-z]` or `[^a-z]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(x) => *x == c,
+            Token::AnyChar => true,
+            Token::AnySeq => unreachable!(),
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|&(a, b)| a <= c && c <= b) != *negated
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Component {
+    Literal(String),
+    Wildcard(Vec<Token>),
+    /// `**`, matches zero or more directories.
+    AnyDirs,
+}
+
+fn invalid_pattern(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.to_string())
+}
+
+fn parse_class(chars: &mut impl Iterator<Item = char>) -> io::Result<Token> {
+    let mut chars = chars.peekable();
+    let negated = chars.next_if(|&c| c == '!' || c == '^').is_some();
+    let mut ranges = Vec::default();
+    let mut first = true;
+    loop {
+        let c = chars
+            .next()
+            .ok_or_else(|| invalid_pattern("unclosed character class"))?;
+        if c == ']' && !first {
+            return Ok(Token::Class { negated, ranges });
+        }
+        first = false;
+        let end = if chars.next_if_eq(&'-').is_some() {
+            match chars.next() {
+                Some(']') => {
+                    ranges.push((c, c));
+                    ranges.push(('-', '-'));
+                    return Ok(Token::Class { negated, ranges });
+                }
+                Some(end) => end,
+                None => return Err(invalid_pattern("unclosed character class")),
+            }
+        } else {
+            c
+        };
+        ranges.push((c, end));
+    }
+}
+
+fn parse_component(s: &str) -> io::Result<Component> {
+    if s == "**" {
+        return Ok(Component::AnyDirs);
+    }
+    let mut tokens = Vec::default();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '?' => Token::AnyChar,
+            '*' => {
+                if tokens.last() == Some(&Token::AnySeq) {
+                    continue;
+                }
+                Token::AnySeq
+            }
+            '[' => parse_class(&mut chars)?,
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    if tokens.iter().all(|t| matches!(t, Token::Char(_))) {
+        Ok(Component::Literal(s.to_string()))
+    } else {
+        Ok(Component::Wildcard(tokens))
+    }
+}
+
+/// Matches `name` against `tokens`, backtracking to the last `*` on a mismatch.
+fn match_tokens(tokens: &[Token], name: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    let (mut t, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match tokens.get(t) {
+            Some(Token::AnySeq) => {
+                star = Some((t, n));
+                t += 1;
+            }
+            Some(token) if token.matches(name[n]) => {
+                t += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((st, sn)) => {
+                    star = Some((st, sn + 1));
+                    t = st + 1;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[t..].iter().all(|t| *t == Token::AnySeq)
+}
+
+impl Component {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Component::Literal(s) => s == name,
+            Component::Wildcard(tokens) => match_tokens(tokens, name),
+            Component::AnyDirs => true,
+        }
+    }
+}
+
+fn match_components(pattern: &[Component], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((Component::AnyDirs, rest)) => {
+            (0..=path.len()).any(|i| match_components(rest, &path[i..]))
+        }
+        Some((c, rest)) => match path.split_first() {
+            Some((name, path)) => c.matches(name) && match_components(rest, path),
+            None => false,
+        },
+    }
+}
+
+/// Joins with `/`, which all platforms and `VirtualIo` accept, unlike [`Path::join`] that
+/// uses `\\` on Windows.
+fn join(dir: &Path, name: &OsStr) -> PathBuf {
+    let mut path = dir.as_os_str().to_owned();
+    if !path.is_empty() && !path.as_encoded_bytes().ends_with(b"/") {
+        path.push("/");
+    }
+    path.push(name);
+    path.into()
+}
+
+/// Returns the file name for matching. Non UTF-8 names are matched lossily, but results are
+/// built from the original names.
+fn name(path: &Path) -> String {
+    path.file_name()
+        .map(OsStr::to_string_lossy)
+        .unwrap_or_default()
+        .into_owned()
+}
+
+/// A compiled glob pattern such as `cas/**/*.blk`.
+///
+/// Components are separated by `/`. Within a component `?` matches any character,
+/// `*` matches any sequence of characters and `[...]` matches a character class. A
+/// component that is exactly `**` matches zero or more directories. A `..` component is
+/// kept and resolved by the file system, so `d/../e/*` doesn't match `d/e/x`. As in
+/// [`Path::components`], `.` is ignored unless it starts the pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    absolute: bool,
+    components: Vec<Component>,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> io::Result<Self> {
+        let components = pattern
+            .split('/')
+            .enumerate()
+            .filter(|&(i, s)| !s.is_empty() && (s != "." || i == 0))
+            .map(|(_, s)| parse_component(s))
+            .collect::<io::Result<_>>()?;
+        Ok(Self {
+            absolute: pattern.starts_with('/'),
+            components,
+        })
+    }
+    /// Returns `true` if the whole `path` matches the pattern.
+    pub fn matches(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        if path.has_root() != self.absolute {
+            return false;
+        }
+        let path: Vec<String> = path
+            .components()
+            .filter_map(|c| match c {
+                PathComponent::Normal(s) => Some(s.to_string_lossy().into_owned()),
+                PathComponent::CurDir => Some(".".to_string()),
+                PathComponent::ParentDir => Some("..".to_string()),
+                PathComponent::Prefix(_) | PathComponent::RootDir => None,
+            })
+            .collect();
+        match_components(&self.components, &path)
+    }
+    /// Returns all existing paths that match the pattern, sorted.
+    pub fn glob(&self, io: &impl Io) -> io::Result<Vec<PathBuf>> {
+        let root = if self.absolute {
+            PathBuf::from("/")
+        } else {
+            PathBuf::default()
+        };
+        let mut result = Vec::default();
+        expand(io, &root, &self.components, &mut result)?;
+        result.sort();
+        result.dedup();
+        Ok(result)
+    }
+}
+
+/// Returns entries of `dir` or nothing if it doesn't exist.
+fn read_dir<'a, T: Io>(
+    io: &'a T,
+    dir: &Path,
+) -> io::Result<impl Iterator<Item = io::Result<T::DirEntry>> + 'a> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let entries = match io.read_dir(dir) {
+        Ok(x) => Some(x),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    Ok(entries.into_iter().flatten())
+}
+
+fn is_dir(io: &impl Io, path: &Path) -> bool {
+    io.metadata(path).is_ok_and(|m| m.is_dir())
+}
+
+fn expand(
+    io: &impl Io,
+    dir: &Path,
+    components: &[Component],
+    result: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let Some((first, rest)) = components.split_first() else {
+        result.push(dir.to_path_buf());
+        return Ok(());
+    };
+    match first {
+        Component::Literal(name) => {
+            let path = join(dir, name.as_ref());
+            if rest.is_empty() {
+                if io.symlink_metadata(&path).is_ok() {
+                    result.push(path);
+                }
+            } else if is_dir(io, &path) {
+                expand(io, &path, rest, result)?;
+            }
+        }
+        Component::Wildcard(_) => {
+            for entry in read_dir(io, dir)? {
+                let entry = entry?;
+                let entry_path = entry.path();
+                if !first.matches(&name(&entry_path)) {
+                    continue;
+                }
+                let path = join(dir, entry_path.file_name().unwrap_or_default());
+                if rest.is_empty() {
+                    result.push(path);
+                } else if is_dir(io, &path) {
+                    expand(io, &path, rest, result)?;
+                }
+            }
+        }
+        Component::AnyDirs => {
+            if !rest.is_empty() || !dir.as_os_str().is_empty() {
+                expand(io, dir, rest, result)?;
+            }
+            for entry in read_dir(io, dir)? {
+                let entry = entry?;
+                let path = join(dir, entry.path().file_name().unwrap_or_default());
+                // symbolic links are not followed to avoid loops.
+                if entry.metadata()?.is_dir() {
+                    expand(io, &path, components, result)?;
+                } else if rest.is_empty() {
+                    result.push(path);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns all existing paths that match `pattern`, see [`Pattern`].
+pub fn glob(io: &impl Io, pattern: &str) -> io::Result<Vec<PathBuf>> {
+    Pattern::new(pattern)?.glob(io)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::Pattern;
+
+    #[test]
+    fn test_matches() {
+        let p = Pattern::new("cas/**/*.blk").unwrap();
+        assert!(p.matches("cas/a.blk"));
+        assert!(p.matches("cas/x/y/a.blk"));
+        assert!(!p.matches("cas/x/a.txt"));
+        assert!(!p.matches("/cas/a.blk"));
+        let p = Pattern::new("a?c*[0-9][!x]").unwrap();
+        assert!(p.matches("abc15"));
+        assert!(p.matches("a-cxyz9y"));
+        assert!(!p.matches("abc1x"));
+        assert!(!p.matches("ac1y"));
+        assert!(Pattern::new("[]-]").unwrap().matches("]"));
+        assert!(Pattern::new("[a-]").unwrap().matches("-"));
+        assert!(Pattern::new("*a*b").unwrap().matches("xaxxab"));
+        assert!(!Pattern::new("*a*b").unwrap().matches("xaxxa"));
+        assert!(Pattern::new("/**").unwrap().matches("/x/y"));
+        let p = Pattern::new("d/../e/*").unwrap();
+        assert!(p.matches("d/../e/x"));
+        assert!(!p.matches("d/e/x"));
+        assert!(!p.matches("e/x"));
+        assert!(Pattern::new("./d/./*").unwrap().matches("./d/x"));
+        assert!(!Pattern::new("./d/*").unwrap().matches("d/x"));
+        assert_eq!(
+            Pattern::new("a[bc").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
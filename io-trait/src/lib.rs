@@ -1,83 +1,182 @@
 mod async_io;
 mod dir_entry;
 mod file;
+mod glob;
 mod metadata;
+mod open_options;
+mod walk_dir;
 
 pub use async_io::*;
 pub use dir_entry::DirEntry;
 pub use file::File;
-pub use metadata::Metadata;
+pub use glob::{glob, Pattern};
+pub use metadata::{FileType, Metadata};
+pub use open_options::OpenOptions;
+pub use walk_dir::{WalkDir, WalkEntry};
 
 use std::{
+    env::VarError,
+    ffi::OsString,
     io::{self, Read, Write},
     ops::Sub,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
     time::Duration,
 };
 
-fn write_rec(io: &impl Io, p: &str, path: &str, data: &[u8]) -> io::Result<()> {
+fn write_rec(io: &impl Io, p: &Path, path: &Path, data: &[u8]) -> io::Result<()> {
     io.create_dir_recursively(p)?;
     io.write(path, data)
 }
 
 pub trait Io: Sized {
     type Args: Iterator<Item = String>;
+    type Vars: Iterator<Item = (String, String)>;
     type File: File;
+    type Stdin: Read;
     type Stdout: Write;
+    type Stderr: Write;
     type Metadata: Metadata;
-    type DirEntry: DirEntry;
+    type DirEntry: DirEntry<Metadata = Self::Metadata>;
+    type ReadDir<'a>: Iterator<Item = io::Result<Self::DirEntry>>
+    where
+        Self: 'a;
     type Instant: Sub<Output = Duration> + Clone;
     fn args(&self) -> Self::Args;
+    fn var(&self, key: &str) -> Result<String, VarError>;
+    fn vars(&self) -> Self::Vars;
+    fn set_var(&self, key: &str, value: &str);
+    fn remove_var(&self, key: &str);
+    fn stdin(&self) -> Self::Stdin;
     fn stdout(&self) -> Self::Stdout;
-    fn metadata(&self, path: &str) -> io::Result<Self::Metadata>;
-    fn create_dir(&self, path: &str) -> io::Result<()>;
-    fn create(&self, path: &str) -> io::Result<Self::File>;
-    fn open(&self, path: &str) -> io::Result<Self::File>;
+    fn stderr(&self) -> Self::Stderr;
+    fn metadata(&self, path: impl AsRef<Path>) -> io::Result<Self::Metadata>;
+    /// Same as [`Io::metadata`] but doesn't follow a symbolic link at `path`.
+    fn symlink_metadata(&self, path: impl AsRef<Path>) -> io::Result<Self::Metadata>;
+    fn create_dir(&self, path: impl AsRef<Path>) -> io::Result<()>;
+    fn open_with(&self, path: impl AsRef<Path>, options: &OpenOptions) -> io::Result<Self::File>;
+    fn create(&self, path: impl AsRef<Path>) -> io::Result<Self::File> {
+        self.open_with(
+            path,
+            OpenOptions::new().write(true).create(true).truncate(true),
+        )
+    }
+    fn open(&self, path: impl AsRef<Path>) -> io::Result<Self::File> {
+        self.open_with(path, OpenOptions::new().read(true))
+    }
     fn now(&self) -> Self::Instant;
-    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+    fn read(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
         let mut result = Vec::default();
         self.open(path)?.read_to_end(&mut result)?;
         Ok(result)
     }
-    fn read_dir(&self, path: &str) -> io::Result<Vec<Self::DirEntry>>;
-    fn read_to_string(&self, path: &str) -> io::Result<String> {
+    /// Returns an iterator over the entries of a directory. Entries are not buffered,
+    /// see [`Io::read_dir_vec`] to collect them.
+    fn read_dir(&self, path: impl AsRef<Path>) -> io::Result<Self::ReadDir<'_>>;
+    fn read_dir_vec(&self, path: impl AsRef<Path>) -> io::Result<Vec<Self::DirEntry>> {
+        self.read_dir(path)?.collect()
+    }
+    fn read_to_string(&self, path: impl AsRef<Path>) -> io::Result<String> {
         let mut result = String::default();
         self.open(path)?.read_to_string(&mut result)?;
         Ok(result)
     }
-    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+    fn write(&self, path: impl AsRef<Path>, data: &[u8]) -> io::Result<()> {
         self.create(path)?.write_all(data)
     }
-    fn create_dir_recursively(&self, path: &str) -> io::Result<()> {
-        let mut x = String::default();
+    fn create_dir_recursively(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        // components are joined with `/`, which all platforms accept, unlike `PathBuf::push`
+        // that uses `\` on Windows.
+        let mut x = OsString::default();
+        let mut separator = false;
         let mut e = Ok(());
-        for i in path.split('/') {
-            x += i;
+        for i in path.as_ref().components() {
+            if separator {
+                x.push("/");
+            }
+            x.push(i.as_os_str());
+            separator = matches!(
+                i,
+                Component::Normal(_) | Component::CurDir | Component::ParentDir
+            );
             e = self.create_dir(&x);
-            x += "/";
         }
         e
     }
-    fn write_recursively(&self, path: &str, data: &[u8]) -> io::Result<()> {
+    fn write_recursively(&self, path: impl AsRef<Path>, data: &[u8]) -> io::Result<()> {
+        let path = path.as_ref();
         let e = self.write(path, data);
         if let Err(er) = e {
-            if let Some((p, _)) = path.rsplit_once('/') {
-                write_rec(self, p, path, data)
-            } else {
-                Err(er)
+            match path.parent() {
+                Some(p) if !p.as_os_str().is_empty() => write_rec(self, p, path, data),
+                _ => Err(er),
             }
         } else {
             Ok(())
         }
     }
-    fn read_dir_type(&self, path: &str, is_dir: bool) -> io::Result<Vec<Self::DirEntry>> {
+    fn read_dir_type(
+        &self,
+        path: impl AsRef<Path>,
+        is_dir: bool,
+    ) -> io::Result<Vec<Self::DirEntry>> {
         let mut result = Vec::default();
         for i in self.read_dir(path)? {
+            let i = i?;
             if i.metadata()?.is_dir() == is_dir {
                 result.push(i);
             }
         }
         Ok(result)
     }
-    fn current_dir(&self) -> io::Result<String>;
-    fn set_current_dir(&self, path: &str) -> io::Result<()>;
+    /// Recursively iterates over `root` and its contents, see [`WalkDir`] for options.
+    fn walk_dir(&self, root: impl AsRef<Path>) -> WalkDir<'_, Self> {
+        WalkDir::new(self, root)
+    }
+    fn current_dir(&self) -> io::Result<PathBuf>;
+    fn set_current_dir(&self, path: impl AsRef<Path>) -> io::Result<()>;
+    /// Makes creations, removals and renames of entries in the directory durable. Syncing a
+    /// file doesn't sync its directory entry.
+    fn sync_dir(&self, path: impl AsRef<Path>) -> io::Result<()>;
+    fn remove_file(&self, path: impl AsRef<Path>) -> io::Result<()>;
+    /// Removes an empty directory.
+    fn remove_dir(&self, path: impl AsRef<Path>) -> io::Result<()>;
+    /// Removes a directory after removing all its contents.
+    fn remove_dir_all(&self, path: impl AsRef<Path>) -> io::Result<()>;
+    fn set_read_only(&self, path: impl AsRef<Path>, read_only: bool) -> io::Result<()>;
+    /// Creates a symbolic link at `link` pointing to `original`. A relative `original` is
+    /// resolved against the directory of `link`.
+    fn symlink(&self, original: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()>;
+    fn hard_link(&self, original: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()>;
+    fn read_link(&self, path: impl AsRef<Path>) -> io::Result<PathBuf>;
+    /// Returns the absolute path with all intermediate components normalized and
+    /// symbolic links resolved.
+    fn canonicalize(&self, path: impl AsRef<Path>) -> io::Result<PathBuf>;
+    /// Renames a file or a directory, replacing `to` if it already exists.
+    fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()>;
+    /// Writes `data` into a temporary sibling file, syncs it and renames it over `path`,
+    /// so `path` contains either the old or the new data, never a partially written one.
+    fn write_atomic(&self, path: impl AsRef<Path>, data: &[u8]) -> io::Result<()> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let path = path.as_ref();
+        // a new name is tried until it doesn't exist, so neither an existing file nor the
+        // temporary file of a concurrent call is overwritten.
+        let (tmp, mut file) = loop {
+            let mut tmp = path.as_os_str().to_owned();
+            tmp.push(format!(".{}.tmp", COUNTER.fetch_add(1, Ordering::Relaxed)));
+            match self.open_with(&tmp, OpenOptions::new().write(true).create_new(true)) {
+                Ok(file) => break (tmp, file),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        };
+        let result = file.write_all(data).and_then(|_| file.sync_all());
+        // the file is closed before it's renamed.
+        drop(file);
+        let result = result.and_then(|_| self.rename(&tmp, path));
+        if result.is_err() {
+            let _ = self.remove_file(&tmp);
+        }
+        result
+    }
 }
@@ -1,30 +1,108 @@
-use std::fs;
+use std::{fs, io, time::SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Dir,
+    Symlink,
+    /// Sockets, pipes, devices and other special files.
+    Other,
+}
+
+impl FileType {
+    pub fn is_file(self) -> bool {
+        self == FileType::File
+    }
+    pub fn is_dir(self) -> bool {
+        self == FileType::Dir
+    }
+    pub fn is_symlink(self) -> bool {
+        self == FileType::Symlink
+    }
+}
+
+impl From<fs::FileType> for FileType {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_file() {
+            FileType::File
+        } else if file_type.is_dir() {
+            FileType::Dir
+        } else if file_type.is_symlink() {
+            FileType::Symlink
+        } else {
+            FileType::Other
+        }
+    }
+}
 
 #[allow(clippy::len_without_is_empty)]
 pub trait Metadata {
     fn len(&self) -> u64;
-    fn is_dir(&self) -> bool;
+    fn file_type(&self) -> FileType;
+    fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+    fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+    fn is_symlink(&self) -> bool {
+        self.file_type().is_symlink()
+    }
+    fn is_read_only(&self) -> bool;
+    fn modified(&self) -> io::Result<SystemTime>;
+    fn accessed(&self) -> io::Result<SystemTime>;
+    /// Not all platforms track the creation time, in which case an error is returned.
+    fn created(&self) -> io::Result<SystemTime>;
 }
 
 impl Metadata for fs::Metadata {
     fn len(&self) -> u64 {
         self.len()
     }
+    fn file_type(&self) -> FileType {
+        self.file_type().into()
+    }
     fn is_dir(&self) -> bool {
         self.is_dir()
     }
+    fn is_read_only(&self) -> bool {
+        self.permissions().readonly()
+    }
+    fn modified(&self) -> io::Result<SystemTime> {
+        self.modified()
+    }
+    fn accessed(&self) -> io::Result<SystemTime> {
+        self.accessed()
+    }
+    fn created(&self) -> io::Result<SystemTime> {
+        self.created()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::fs;
 
-    use crate::Metadata;
+    use crate::{FileType, Metadata};
 
     #[test]
     fn test() {
         let m = fs::metadata("Cargo.toml").unwrap();
         assert_ne!(Metadata::len(&m), 0);
-        assert_eq!(Metadata::is_dir(&m), false);
+        assert!(!Metadata::is_dir(&m));
+        assert_eq!(Metadata::file_type(&m), FileType::File);
+        assert!(Metadata::is_file(&m));
+        assert!(!Metadata::is_symlink(&m));
+        assert!(!Metadata::is_read_only(&m));
+        assert_eq!(Metadata::modified(&m).unwrap(), m.modified().unwrap());
+        assert_eq!(Metadata::accessed(&m).unwrap(), m.accessed().unwrap());
+        let _ = Metadata::created(&m);
+    }
+
+    #[test]
+    fn test_dir() {
+        let m = fs::metadata("src").unwrap();
+        assert!(Metadata::is_dir(&m));
+        assert_eq!(Metadata::file_type(&m), FileType::Dir);
     }
 }
@@ -0,0 +1,119 @@
+use std::{fs, io};
+
+/// Options that configure how a file is opened, see [`std::fs::OpenOptions`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OpenOptions {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub truncate: bool,
+    pub create: bool,
+    pub create_new: bool,
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+    /// Returns `true` if a file opened with these options can be written.
+    pub fn is_writable(&self) -> bool {
+        self.write || self.append
+    }
+    /// Rejects the same invalid combinations as [`std::fs::OpenOptions::open`].
+    pub fn check(&self) -> io::Result<()> {
+        let invalid = |message: &str| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                message.to_string(),
+            ))
+        };
+        if !self.read && !self.is_writable() {
+            return invalid("no access mode is set");
+        }
+        if (self.truncate || self.create || self.create_new) && !self.is_writable() {
+            return invalid("creating or truncating a file requires write access");
+        }
+        if self.truncate && self.append && !self.create_new {
+            return invalid("truncate and append can't be used together");
+        }
+        Ok(())
+    }
+}
+
+impl From<&OpenOptions> for fs::OpenOptions {
+    fn from(options: &OpenOptions) -> Self {
+        let mut result = fs::OpenOptions::new();
+        result
+            .read(options.read)
+            .write(options.write)
+            .append(options.append)
+            .truncate(options.truncate)
+            .create(options.create)
+            .create_new(options.create_new);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::OpenOptions;
+
+    #[test]
+    fn test_check() {
+        assert!(OpenOptions::new().read(true).check().is_ok());
+        assert!(OpenOptions::new().append(true).create(true).check().is_ok());
+        assert_eq!(
+            OpenOptions::new().check().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(OpenOptions::new()
+            .read(true)
+            .truncate(true)
+            .check()
+            .is_err());
+        assert!(OpenOptions::new()
+            .read(true)
+            .create_new(true)
+            .check()
+            .is_err());
+        assert!(OpenOptions::new()
+            .append(true)
+            .truncate(true)
+            .check()
+            .is_err());
+    }
+
+    #[test]
+    fn test_std() {
+        let mut o = OpenOptions::new();
+        o.read(true);
+        let f = std::fs::OpenOptions::from(&o).open("Cargo.toml").unwrap();
+        assert!(f.metadata().unwrap().len() > 0);
+    }
+}
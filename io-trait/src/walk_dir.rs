@@ -0,0 +1,226 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{DirEntry, Io, Metadata};
+
+/// An entry yielded by [`WalkDir`].
+#[derive(Debug)]
+pub struct WalkEntry<M> {
+    path: PathBuf,
+    depth: usize,
+    metadata: M,
+}
+
+impl<M: Metadata> WalkEntry<M> {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+    /// The root has depth `0`, its children have depth `1` and so on.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    /// Metadata of the symbolic link target if links are followed.
+    pub fn metadata(&self) -> &M {
+        &self.metadata
+    }
+}
+
+type Children<'a, T> = Box<dyn Iterator<Item = io::Result<<T as Io>::DirEntry>> + 'a>;
+
+struct Frame<'a, T: Io> {
+    /// The directory itself, if it's yielded after its contents.
+    entry: Option<WalkEntry<T::Metadata>>,
+    children: Children<'a, T>,
+    depth: usize,
+    canonical: Option<PathBuf>,
+}
+
+type Filter<'a, M> = Box<dyn FnMut(&WalkEntry<M>) -> bool + 'a>;
+
+/// A recursive directory iterator created by [`Io::walk_dir`].
+pub struct WalkDir<'a, T: Io> {
+    io: &'a T,
+    root: Option<PathBuf>,
+    min_depth: usize,
+    max_depth: usize,
+    contents_first: bool,
+    sort: bool,
+    follow_links: bool,
+    filter: Option<Filter<'a, T::Metadata>>,
+    stack: Vec<Frame<'a, T>>,
+}
+
+impl<'a, T: Io> WalkDir<'a, T> {
+    pub fn new(io: &'a T, root: impl AsRef<Path>) -> Self {
+        Self {
+            io,
+            root: Some(root.as_ref().to_path_buf()),
+            min_depth: 0,
+            max_depth: usize::MAX,
+            contents_first: false,
+            sort: false,
+            follow_links: false,
+            filter: None,
+            stack: Vec::default(),
+        }
+    }
+    /// Entries with a smaller depth are not yielded but are still traversed.
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = depth;
+        self
+    }
+    /// Directories at this depth are not traversed.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+    /// Yields directory contents before the directory itself (post-order).
+    pub fn contents_first(mut self, contents_first: bool) -> Self {
+        self.contents_first = contents_first;
+        self
+    }
+    /// Yields entries of each directory sorted by their paths.
+    pub fn sort(mut self, sort: bool) -> Self {
+        self.sort = sort;
+        self
+    }
+    /// Traverses directories behind symbolic links. A link that leads to one of its
+    /// ancestors is reported as an error instead.
+    pub fn follow_links(mut self, follow_links: bool) -> Self {
+        self.follow_links = follow_links;
+        self
+    }
+    /// Skips entries for which `filter` returns `false`, including their contents.
+    pub fn filter_entry(
+        mut self,
+        filter: impl FnMut(&WalkEntry<T::Metadata>) -> bool + 'a,
+    ) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+    fn root_entry(&self, path: PathBuf) -> io::Result<WalkEntry<T::Metadata>> {
+        Ok(WalkEntry {
+            metadata: self.io.metadata(&path)?,
+            path,
+            depth: 0,
+        })
+    }
+    fn child_entry(&self, entry: T::DirEntry, depth: usize) -> io::Result<WalkEntry<T::Metadata>> {
+        let path = entry.path();
+        let mut metadata = entry.metadata()?;
+        if self.follow_links && metadata.is_symlink() {
+            // a broken link is yielded as a link.
+            if let Ok(m) = self.io.metadata(&path) {
+                metadata = m;
+            }
+        }
+        Ok(WalkEntry {
+            path,
+            depth,
+            metadata,
+        })
+    }
+    fn yield_entry(
+        &self,
+        entry: WalkEntry<T::Metadata>,
+    ) -> Option<io::Result<WalkEntry<T::Metadata>>> {
+        if entry.depth >= self.min_depth {
+            Some(Ok(entry))
+        } else {
+            None
+        }
+    }
+    fn handle(
+        &mut self,
+        entry: WalkEntry<T::Metadata>,
+    ) -> Option<io::Result<WalkEntry<T::Metadata>>> {
+        if let Some(filter) = &mut self.filter {
+            if !filter(&entry) {
+                return None;
+            }
+        }
+        if !entry.metadata.is_dir() || entry.depth >= self.max_depth {
+            return self.yield_entry(entry);
+        }
+        let canonical = if self.follow_links {
+            match self.io.canonicalize(&entry.path) {
+                Ok(c) => {
+                    if self.stack.iter().any(|f| f.canonical.as_ref() == Some(&c)) {
+                        return Some(Err(io::Error::other(format!(
+                            "file system loop found: {}",
+                            entry.path.display()
+                        ))));
+                    }
+                    Some(c)
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        } else {
+            None
+        };
+        let children = match self.io.read_dir(&entry.path) {
+            Ok(children) => children,
+            Err(e) => return Some(Err(e)),
+        };
+        let children: Children<'a, T> = if self.sort {
+            let mut children = match children.collect::<io::Result<Vec<_>>>() {
+                Ok(children) => children,
+                Err(e) => return Some(Err(e)),
+            };
+            children.sort_by_cached_key(DirEntry::path);
+            Box::new(children.into_iter().map(Ok))
+        } else {
+            Box::new(children)
+        };
+        let depth = entry.depth + 1;
+        let (entry, result) = if self.contents_first {
+            (Some(entry), None)
+        } else {
+            (None, self.yield_entry(entry))
+        };
+        self.stack.push(Frame {
+            entry,
+            children,
+            depth,
+            canonical,
+        });
+        result
+    }
+}
+
+impl<T: Io> Iterator for WalkDir<'_, T> {
+    type Item = io::Result<WalkEntry<T::Metadata>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            let result = match self.root_entry(root) {
+                Ok(entry) => self.handle(entry),
+                Err(e) => Some(Err(e)),
+            };
+            if result.is_some() {
+                return result;
+            }
+        }
+        loop {
+            let frame = self.stack.last_mut()?;
+            let depth = frame.depth;
+            let result = match frame.children.next() {
+                Some(child) => match child.and_then(|c| self.child_entry(c, depth)) {
+                    Ok(entry) => self.handle(entry),
+                    Err(e) => Some(Err(e)),
+                },
+                None => {
+                    let entry = self.stack.pop().and_then(|f| f.entry);
+                    entry.and_then(|e| self.yield_entry(e))
+                }
+            };
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+}